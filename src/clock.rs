@@ -0,0 +1,96 @@
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use futures::future::{self, BoxFuture};
+use futures::FutureExt;
+
+/// Abstraction over the time, used by the retry backoff and the token expiry checks
+///
+/// The `SystemClock` is used by default, whereas the `MockClock` can be injected via the
+/// `DockerHubClientBuilder::clock` so that the tests can advance the time deterministically
+/// and assert the retry timing without actually sleeping.
+pub trait Clock: Debug + Send + Sync {
+    /// Returns the current time
+    fn now(&self) -> DateTime<Utc>;
+
+    /// Waits until the provided duration has elapsed
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()>;
+}
+
+/// The real clock, relying on the system time and on the tokio timers
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+        tokio::time::sleep(duration).boxed()
+    }
+}
+
+/// A fake clock whose time only moves when advanced, either explicitly via `advance` or when
+/// sleeping, in which case the sleep completes right away
+///
+/// The clones of a `MockClock` share the same time, so that a clone can be kept in the tests
+/// to inspect and advance the time of the clock injected within the client.
+#[derive(Clone, Debug)]
+pub struct MockClock {
+    now: Arc<Mutex<DateTime<Utc>>>,
+    sleeps: Arc<Mutex<Vec<Duration>>>,
+}
+
+impl MockClock {
+    /// Creates a new `MockClock` starting at the provided time
+    pub fn new(now: DateTime<Utc>) -> Self {
+        Self {
+            now: Arc::new(Mutex::new(now)),
+            sleeps: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// Moves the time forward by the provided duration
+    pub fn advance(&self, duration: Duration) {
+        let mut now = self.now.lock().unwrap();
+        *now += duration;
+    }
+
+    /// Returns the durations of all the sleeps so far, in order
+    pub fn sleeps(&self) -> Vec<Duration> {
+        self.sleeps.lock().unwrap().clone()
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.now.lock().unwrap()
+    }
+
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+        self.sleeps.lock().unwrap().push(duration);
+        self.advance(duration);
+        future::ready(()).boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_mock_clock() {
+        let start = DateTime::from_timestamp(1741600000, 0).unwrap();
+        let clock = MockClock::new(start);
+        let handle = clock.clone();
+
+        clock.sleep(Duration::from_secs(5)).await;
+        handle.advance(Duration::from_secs(10));
+
+        assert_eq!(clock.now(), start + Duration::from_secs(15));
+        assert_eq!(handle.sleeps(), vec![Duration::from_secs(5)]);
+    }
+}
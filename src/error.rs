@@ -0,0 +1,371 @@
+use chrono::{DateTime, Utc};
+use reqwest::StatusCode;
+use url::Url;
+
+/// The maximum number of characters of the body included in `HubError::UnexpectedContentType`
+pub const SNIPPET_LENGTH: usize = 200;
+
+/// The specific errors that the Docker Hub client may fail with
+///
+/// These are returned as is by the `fetch` family of functions and by the `list_*` methods of
+/// the client, so that the callers can match on the variants e.g. to tell a rate limit apart from
+/// a missing resource; whereas the rest of the methods return those wrapped within an
+/// `anyhow::Error`, in which case the callers can `downcast_ref::<HubError>()` instead. Note that
+/// the errors may be wrapped within a `HubError::Response` carrying the request id and the
+/// diagnostic headers, so `HubError::inner` should be used to match on the actual failure.
+#[derive(Debug, thiserror::Error)]
+pub enum HubError {
+    /// The response was not JSON, as it happens when a proxy, a WAF, or a captive portal sits
+    /// between the client and the Docker Hub and replies with an HTML error page instead
+    #[error(
+        "expected a JSON response but got {} (is there a proxy in between?): {snippet}",
+        content_type.as_deref().unwrap_or("no content type")
+    )]
+    UnexpectedContentType {
+        /// The `Content-Type` of the response, if any
+        content_type: Option<String>,
+
+        /// The beginning of the response body, truncated to `SNIPPET_LENGTH` characters
+        snippet: String,
+    },
+
+    /// The Docker Hub responded with a status code that's not handled otherwise e.g. a 500
+    #[error("request to {url} failed with status code {status}")]
+    Http {
+        /// The status code of the response
+        status: StatusCode,
+
+        /// The URL of the request
+        url: Url,
+
+        /// The error found while reading the response body, if any e.g. a
+        /// `HubError::UnexpectedContentType` for an HTML error page sent by a proxy
+        source: Option<Box<HubError>>,
+    },
+
+    /// The available requests are exhausted i.e. the Docker Hub responded with a 429
+    #[error("{}", rate_limited_message(url, retry_after))]
+    RateLimited {
+        /// The URL of the request
+        url: Url,
+
+        /// When the requests can be sent again, as per the `X-Retry-After` header, if any
+        retry_after: Option<DateTime<Utc>>,
+    },
+
+    /// The requested resource doesn't exist i.e. the Docker Hub responded with a 404
+    #[error("{0} not found")]
+    NotFound(Url),
+
+    /// The provided token is missing or not valid i.e. the Docker Hub responded with a 401
+    #[error("provided client is not authorized to request {0}")]
+    Unauthorized(Url),
+
+    /// The provided token is valid but has no access to the resource i.e. the Docker Hub
+    /// responded with a 403
+    #[error("access to {0} is forbidden for the provided token")]
+    Forbidden(Url),
+
+    /// The response couldn't be parsed into the expected struct
+    #[error("parsing the output json into an `T` struct failed at `{path}`: {source}")]
+    Deserialization {
+        /// The JSON path of the offending field e.g. `results[3].images[0].last_pushed`
+        path: String,
+
+        /// The underlying parsing error
+        source: serde_json::Error,
+    },
+
+    /// The request couldn't be sent or its response couldn't be read e.g. a connection error
+    #[error("failed with error {0}")]
+    Request(#[from] reqwest::Error),
+
+    /// Any other failure e.g. a URL that couldn't be formatted
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+
+    /// The request failed with the `error` within, along with the details to correlate it with
+    /// the Docker Hub diagnostics e.g. when reporting it to the Docker support
+    #[error("{}", response_details(request_id, diagnostics, *retries))]
+    Response {
+        /// The actual failure of the request
+        #[source]
+        error: Box<HubError>,
+
+        /// The value of the `X-Request-ID` header sent, if enabled via the builder
+        request_id: Option<String>,
+
+        /// The diagnostic headers of the response as `name: value` pairs, if any
+        diagnostics: Option<String>,
+
+        /// The number of retries sent before giving up, zero if the request was not retried
+        retries: usize,
+    },
+}
+
+/// Formats the message of `HubError::Response` with the details that are available
+fn response_details(
+    request_id: &Option<String>,
+    diagnostics: &Option<String>,
+    retries: usize,
+) -> String {
+    let mut details = Vec::new();
+    if retries > 0 {
+        details.push(format!("giving up after {retries} retries"));
+    }
+    if let Some(request_id) = request_id {
+        details.push(format!("request id: {request_id}"));
+    }
+    if let Some(diagnostics) = diagnostics {
+        details.push(format!("diagnostic headers: {diagnostics}"));
+    }
+    details.join(", ")
+}
+
+/// Formats the message of `HubError::RateLimited`, including the time to retry after if known
+fn rate_limited_message(url: &Url, retry_after: &Option<DateTime<Utc>>) -> String {
+    match retry_after {
+        Some(retry_after) => format!(
+            "available requests exhausted requesting {url}, please try again after {}",
+            retry_after.timestamp()
+        ),
+        None => format!("too many requests sent to the docker hub requesting {url}"),
+    }
+}
+
+impl HubError {
+    /// Creates a `HubError::UnexpectedContentType` truncating the provided `body`
+    pub fn unexpected_content_type(content_type: Option<String>, body: &str) -> Self {
+        let body = body.trim();
+        let mut snippet = body.chars().take(SNIPPET_LENGTH).collect::<String>();
+        if snippet.len() < body.len() {
+            snippet.push('…');
+        }
+        HubError::UnexpectedContentType {
+            content_type,
+            snippet,
+        }
+    }
+
+    /// Creates a `HubError::RateLimited` for the request to `url` from the value of the
+    /// `X-Retry-After` header, if any, which is the unix timestamp of when the requests can be
+    /// sent again
+    pub fn rate_limited(url: &Url, retry_after: Option<&str>) -> Self {
+        HubError::RateLimited {
+            url: url.clone(),
+            retry_after: retry_after
+                .and_then(|value| value.trim().parse::<i64>().ok())
+                .and_then(|timestamp| DateTime::from_timestamp(timestamp, 0)),
+        }
+    }
+
+    /// Returns the status code of the response, if the error comes from an HTTP response e.g.
+    /// to treat all the 5xx uniformly via `StatusCode::is_server_error`
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            HubError::Http { status, .. } => Some(*status),
+            HubError::Response { error, .. } => error.status(),
+            HubError::RateLimited { .. } => Some(StatusCode::TOO_MANY_REQUESTS),
+            HubError::NotFound(_) => Some(StatusCode::NOT_FOUND),
+            HubError::Unauthorized(_) => Some(StatusCode::UNAUTHORIZED),
+            HubError::Forbidden(_) => Some(StatusCode::FORBIDDEN),
+            HubError::Request(e) => e.status(),
+            HubError::Other(e) => e.downcast_ref::<HubError>().and_then(HubError::status),
+            HubError::UnexpectedContentType { .. } | HubError::Deserialization { .. } => None,
+        }
+    }
+
+    /// Returns the URL of the request that failed, if the error comes from an HTTP response
+    pub fn url(&self) -> Option<&Url> {
+        match self {
+            HubError::Http { url, .. }
+            | HubError::RateLimited { url, .. }
+            | HubError::NotFound(url)
+            | HubError::Unauthorized(url)
+            | HubError::Forbidden(url) => Some(url),
+            HubError::Request(e) => e.url(),
+            HubError::Response { error, .. } => error.url(),
+            HubError::Other(e) => e.downcast_ref::<HubError>().and_then(HubError::url),
+            HubError::UnexpectedContentType { .. } | HubError::Deserialization { .. } => None,
+        }
+    }
+
+    /// Returns the actual failure i.e. the error within `HubError::Response` if wrapped, so that
+    /// the callers can match on it regardless of the request details
+    pub fn inner(&self) -> &HubError {
+        match self {
+            HubError::Response { error, .. } => error.inner(),
+            error => error,
+        }
+    }
+
+    /// Wraps the provided `error` within a `HubError::Response` with the provided request id
+    /// and diagnostic headers, unless there are none in which case it's returned as is
+    pub(crate) fn with_details(
+        self,
+        request_id: Option<String>,
+        diagnostics: Option<String>,
+    ) -> Self {
+        if request_id.is_none() && diagnostics.is_none() {
+            return self;
+        }
+        HubError::Response {
+            error: Box::new(self),
+            request_id,
+            diagnostics,
+            retries: 0,
+        }
+    }
+
+    /// Records that the request gave up after the provided number of `retries`
+    pub(crate) fn with_retries(self, retries: usize) -> Self {
+        match self {
+            _ if retries == 0 => self,
+            HubError::Response {
+                error,
+                request_id,
+                diagnostics,
+                ..
+            } => HubError::Response {
+                error,
+                request_id,
+                diagnostics,
+                retries,
+            },
+            error => HubError::Response {
+                error: Box::new(error),
+                request_id: None,
+                diagnostics: None,
+                retries,
+            },
+        }
+    }
+}
+
+/// Returns whether the provided `Content-Type` header value is JSON e.g. "application/json" or
+/// "application/problem+json"
+pub(crate) fn is_json_content_type(content_type: &str) -> bool {
+    let mime = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    mime == "application/json" || mime.ends_with("+json")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_json_content_type() {
+        assert!(is_json_content_type("application/json"));
+        assert!(is_json_content_type("application/json; charset=utf-8"));
+        assert!(is_json_content_type("application/problem+json"));
+        assert!(!is_json_content_type("text/html; charset=UTF-8"));
+        assert!(!is_json_content_type(""));
+    }
+
+    #[test]
+    fn test_unexpected_content_type_snippet() {
+        let body = format!("<html>{}</html>", "a".repeat(500));
+        let error = HubError::unexpected_content_type(Some("text/html".to_string()), &body);
+
+        let HubError::UnexpectedContentType { snippet, .. } = &error else {
+            panic!("expected an unexpected content type error");
+        };
+        assert_eq!(snippet.chars().count(), SNIPPET_LENGTH + 1);
+        assert!(snippet.starts_with("<html>"));
+        assert!(error.to_string().contains("text/html"));
+    }
+
+    #[test]
+    fn test_rate_limited() {
+        let url = Url::parse("https://hub.docker.com/v2/categories").unwrap();
+        let error = HubError::rate_limited(&url, Some("1741600000"));
+        assert!(matches!(
+            error,
+            HubError::RateLimited { retry_after: Some(retry_after), .. }
+                if retry_after.timestamp() == 1741600000
+        ));
+        assert_eq!(
+            error.to_string(),
+            "available requests exhausted requesting https://hub.docker.com/v2/categories, please try again after 1741600000"
+        );
+        assert_eq!(error.url(), Some(&url));
+
+        let error = HubError::rate_limited(&url, Some("soon"));
+        assert!(matches!(
+            error,
+            HubError::RateLimited {
+                retry_after: None,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_http_status() {
+        let error = HubError::Http {
+            status: StatusCode::BAD_GATEWAY,
+            url: Url::parse("https://hub.docker.com/v2/categories").unwrap(),
+            source: None,
+        };
+        assert!(error
+            .status()
+            .is_some_and(|status| status.is_server_error()));
+        assert_eq!(
+            error.to_string(),
+            "request to https://hub.docker.com/v2/categories failed with status code 502 Bad Gateway"
+        );
+
+        // The error remains accessible when wrapped within an `anyhow::Error` with context
+        let error = anyhow::Error::from(error).context("fetching the provided url failed");
+        assert_eq!(
+            error.downcast_ref::<HubError>().and_then(HubError::status),
+            Some(StatusCode::BAD_GATEWAY)
+        );
+    }
+
+    #[test]
+    fn test_response_details() {
+        let url = Url::parse("https://hub.docker.com/v2/categories").unwrap();
+        let error = HubError::NotFound(url.clone()).with_details(None, None);
+        assert!(matches!(error, HubError::NotFound(_)));
+
+        let error = HubError::NotFound(url)
+            .with_details(
+                Some("1234".to_string()),
+                Some("x-trace-id: abcd".to_string()),
+            )
+            .with_retries(2);
+        assert_eq!(
+            error.to_string(),
+            "giving up after 2 retries, request id: 1234, diagnostic headers: x-trace-id: abcd"
+        );
+        assert!(matches!(error.inner(), HubError::NotFound(_)));
+        assert_eq!(error.status(), Some(StatusCode::NOT_FOUND));
+        assert_eq!(
+            format!("{:#}", anyhow::Error::from(error)),
+            "giving up after 2 retries, request id: 1234, diagnostic headers: x-trace-id: abcd: https://hub.docker.com/v2/categories not found"
+        );
+    }
+
+    #[test]
+    fn test_status_variants() {
+        let url = Url::parse("https://hub.docker.com/v2/namespaces/ollama").unwrap();
+        assert_eq!(
+            HubError::NotFound(url.clone()).to_string(),
+            "https://hub.docker.com/v2/namespaces/ollama not found"
+        );
+        assert_eq!(
+            HubError::Forbidden(url.clone()).status(),
+            Some(StatusCode::FORBIDDEN)
+        );
+        assert_eq!(
+            HubError::Unauthorized(url).status(),
+            Some(StatusCode::UNAUTHORIZED)
+        );
+    }
+}
@@ -29,17 +29,56 @@
 //! }
 //! ```
 
+use std::future::Future;
+use std::net::SocketAddr;
+use std::ops::ControlFlow;
+use std::sync::Arc;
+
 use anyhow::Context;
+use chrono::{DateTime, Utc};
 use futures::future::join_all;
-use reqwest::{header, Client};
+use futures::stream::{self, FuturesUnordered, Stream, StreamExt, TryStreamExt};
+use reqwest::{header, Client, Method, RequestBuilder, Response};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use tokio::sync::{OnceCell, Semaphore};
+use tokio::task::{AbortHandle, JoinError};
 use url::Url;
+use uuid::Uuid;
+
+use crate::clock::{Clock, SystemClock};
+use crate::error::{is_json_content_type, HubError};
+use crate::repositories::Category;
+use crate::retry::{retry_with, RetryError, RetryPolicy};
+use crate::utils::{deserialize_one_or_many, jwt_expiration, validate_token};
 
+pub mod clock;
+pub mod error;
+pub mod orgs;
+pub mod prelude;
 pub mod repositories;
+pub mod retry;
+pub mod snapshots;
 pub mod tags;
+pub mod users;
+pub mod utils;
+
+/// The maximum page size allowed by the Docker Hub, larger values are silently clamped to it
+///
+/// This is the page size requested by the sequential pagination i.e. when following the `next`
+/// URL of each page, so that the number of requests is minimized.
+pub const MAX_PAGE_SIZE: usize = 100;
+
+/// The default maximum number of requests sent concurrently to the Docker Hub by the bulk
+/// methods and the pagination, see `DockerHubClientBuilder::max_concurrent_requests`
+pub const MAX_CONCURRENT_REQUESTS: usize = 8;
+
+/// A hook that receives every outgoing request right before it's sent, and returns it back with
+/// any customization applied e.g. extra headers, a signature, or some logging
+pub type Middleware = Arc<dyn Fn(RequestBuilder) -> RequestBuilder + Send + Sync>;
 
 /// Struct that holds the client and the URL to send request to the Docker Hub
+#[derive(Clone)]
 pub struct DockerHubClient {
     /// Contains the instace for the reqwest Client with the required headers and
     /// configuration if any.
@@ -49,9 +88,127 @@ pub struct DockerHubClient {
     // required
     /// Holds the URL for the Docker Hub (https://hub.docker.com)
     pub url: Url,
+
+    /// Caches the categories available on the Docker Hub, as those rarely change, so that
+    /// those are only fetched once per client.
+    categories: OnceCell<Vec<Category>>,
+
+    /// Caches the username of the authenticated user, resolved on the first `whoami` call
+    username: OnceCell<String>,
+
+    /// The org or username to use on the `_in` method variants e.g. `list_tags_in`, if any
+    default_namespace: Option<String>,
+
+    /// The maximum number of requests sent concurrently by the bulk methods and the pagination
+    max_concurrent_requests: usize,
+
+    /// The maximum number of retries for a failed request, see `DockerHubClientBuilder::max_retries`
+    max_retries: usize,
+
+    /// The `Authorization` header with the token sent on every request, if any; which is added
+    /// per request rather than as a default header of the reqwest Client, so that the same
+    /// Client can be shared by an anonymous client, see `without_token`
+    authorization: Option<header::HeaderValue>,
+
+    /// The expiration time of the token if it's a JWT, as PATs don't expire
+    token_expires_at: Option<DateTime<Utc>>,
+
+    /// Whether to send a generated `X-Request-ID` header on every request
+    request_id_header: bool,
+
+    /// The hook applied to every outgoing request before sending it, if any
+    middleware: Option<Middleware>,
+
+    /// The clock used for the retry backoff and the token expiry checks
+    clock: Arc<dyn Clock>,
+}
+
+/// The namespace where the repositories live in on the Docker Hub
+///
+/// Some endpoints differ for users and organizations, so the namespace can be explicitly set to
+/// either `Namespace::User` or `Namespace::Org`; whereas when converted from a plain string, the
+/// namespace is `Namespace::Auto` meaning that the organization endpoint is tried first, falling
+/// back to the user endpoint if the organization is not found.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Namespace {
+    /// The username of a Docker Hub user
+    User(String),
+
+    /// The name of a Docker Hub organization
+    Org(String),
+
+    /// Either a username or an organization name, resolved as an organization first
+    Auto(String),
+
+    /// The personal namespace of the authenticated user, resolved via `DockerHubClient::whoami`
+    Me,
+}
+
+impl Namespace {
+    /// Returns the name of the namespace i.e. the username or the organization name, or `None`
+    /// for `Namespace::Me` as that's only known once resolved via the Docker Hub
+    pub fn name(&self) -> Option<&str> {
+        match self {
+            Namespace::User(name) | Namespace::Org(name) | Namespace::Auto(name) => Some(name),
+            Namespace::Me => None,
+        }
+    }
+}
+
+impl From<&str> for Namespace {
+    fn from(name: &str) -> Self {
+        Namespace::Auto(name.to_string())
+    }
+}
+
+impl From<String> for Namespace {
+    fn from(name: String) -> Self {
+        Namespace::Auto(name)
+    }
+}
+
+impl From<Option<&str>> for Namespace {
+    fn from(name: Option<&str>) -> Self {
+        name.map_or(Namespace::Me, Namespace::from)
+    }
+}
+
+/// A non-fatal issue found while running an operation over multiple items
+#[derive(Debug)]
+pub enum Warning {
+    /// The operation failed for the given item e.g. a repository or a tag, but that didn't
+    /// abort the operation for the rest of the items
+    ItemFailed { item: String, error: anyhow::Error },
+
+    /// The request budget was exhausted before all the pages were fetched, so only part of the
+    /// results were collected; where `next` is the URL of the first page not fetched, which can
+    /// be used as a cursor to resume via `fetch_page_from_cursor`
+    BudgetExceeded { request_budget: usize, next: String },
+}
+
+/// The result of an operation over multiple items that may partially succeed
+///
+/// This is returned by the methods that aggregate the results of multiple requests, so that
+/// both the collected `value` and the non-fatal issues found along the way (e.g. per-item
+/// failures) are returned, rather than aborting the whole operation on the first failure.
+#[derive(Debug, Default)]
+pub struct Outcome<T> {
+    /// The value collected from the items that succeeded
+    pub value: T,
+
+    /// The non-fatal issues found while collecting the value
+    pub warnings: Vec<Warning>,
+}
+
+impl<T> Outcome<T> {
+    /// Returns whether the operation completed without any warnings
+    pub fn is_complete(&self) -> bool {
+        self.warnings.is_empty()
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
+#[serde(from = "RawApiResult<T>")]
 pub struct ApiResult<T> {
     /// Count of the total values that are available, not the `results` length
     count: usize,
@@ -69,6 +226,64 @@ pub struct ApiResult<T> {
     results: Vec<T>,
 }
 
+/// The `ApiResult` as sent by the Docker Hub, where some endpoints (e.g. the registry-style ones)
+/// omit the `count`, in which case it defaults to the length of the `results`; and where some
+/// single-resource endpoints send the `results` as an object rather than as an array
+#[derive(Deserialize)]
+#[serde(bound(deserialize = "T: Deserialize<'de>"))]
+struct RawApiResult<T> {
+    #[serde(default)]
+    count: Option<usize>,
+
+    #[serde(default)]
+    next: Option<String>,
+
+    #[serde(default)]
+    previous: Option<String>,
+
+    #[serde(deserialize_with = "deserialize_one_or_many")]
+    results: Vec<T>,
+}
+
+impl<T> From<RawApiResult<T>> for ApiResult<T> {
+    fn from(raw: RawApiResult<T>) -> Self {
+        Self {
+            count: raw.count.unwrap_or(raw.results.len()),
+            next: raw.next,
+            previous: raw.previous,
+            results: raw.results,
+        }
+    }
+}
+
+impl<T> ApiResult<T> {
+    /// Returns the count of the total values that are available, not the `results` length
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Returns the URL to query the next page of results, if any, which can be persisted and
+    /// used as a cursor to resume the pagination later on via `fetch_page_from_cursor`
+    pub fn next(&self) -> Option<&str> {
+        self.next.as_deref()
+    }
+
+    /// Returns the URL to query the previous page of results, if any
+    pub fn previous(&self) -> Option<&str> {
+        self.previous.as_deref()
+    }
+
+    /// Returns the results within the current page
+    pub fn results(&self) -> &[T] {
+        &self.results
+    }
+
+    /// Consumes the `ApiResult` returning the results within the current page
+    pub fn into_results(self) -> Vec<T> {
+        self.results
+    }
+}
+
 impl DockerHubClient {
     /// Creates a new instance of DockerHubClient with the provided authentication
     ///
@@ -77,119 +292,1959 @@ impl DockerHubClient {
     /// methods within this struct. This method will configure and setup the HTTP client that
     /// will be used within the rest of the methods to send requests to the Docker Hub.
     pub fn new(token: &str) -> anyhow::Result<Self> {
-        let url = Url::parse("https://hub.docker.com").context("couldn't parse docker hub url")?;
+        Self::builder().token(token).build()
+    }
 
-        let mut headers = header::HeaderMap::new();
-        headers.insert(
-            header::AUTHORIZATION,
-            header::HeaderValue::from_str(&format!("Bearer {}", token))
-                .context("couldn't add authorization header with provided token")?,
-        );
+    /// Creates a new instance of DockerHubClient without any authentication
+    ///
+    /// This method creates a client that sends no `Authorization` header, which is enough to
+    /// read the public data on the Docker Hub e.g. the tags of `library/ubuntu`, without having
+    /// to provision a token; whereas the requests to private resources will fail as unauthorized.
+    pub fn anonymous() -> anyhow::Result<Self> {
+        Self::builder().build()
+    }
+
+    /// Creates a new instance of DockerHubClient with the provided authentication, sending the
+    /// requests to the provided `base_url` rather than to `https://hub.docker.com`
+    ///
+    /// This is useful to talk to e.g. a self-hosted or private registry exposing the Docker Hub
+    /// API, optionally under a path prefix e.g. `https://registry.example.com/prefix`, in which
+    /// case the prefix is kept regardless of the trailing slash.
+    pub fn with_base_url(token: &str, base_url: &str) -> anyhow::Result<Self> {
+        let base_url = Url::parse(base_url)
+            .with_context(|| format!("couldn't parse the provided base url: {base_url}"))?;
+        Self::builder().token(token).base_url(base_url).build()
+    }
+
+    /// Creates a new instance of DockerHubClient wrapping a caller-provided reqwest Client
+    ///
+    /// This gives full control over the HTTP behavior e.g. custom middleware, instrumentation,
+    /// or connection pools; while reusing all the Docker Hub methods within this struct. Note
+    /// that no `Authorization` header is injected, so the provided `client` should already
+    /// carry it (if any) e.g. via its default headers; and that the requests are sent to the
+    /// provided `base_url` e.g. `https://hub.docker.com`.
+    pub fn from_client(client: Client, base_url: Url) -> Self {
+        Self {
+            client,
+            url: with_trailing_slash(base_url),
+            categories: OnceCell::new(),
+            username: OnceCell::new(),
+            default_namespace: None,
+            max_concurrent_requests: MAX_CONCURRENT_REQUESTS,
+            max_retries: RetryPolicy::default().max_retries,
+            authorization: None,
+            token_expires_at: None,
+            request_id_header: false,
+            middleware: None,
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Returns the expiration time of the token, or `None` for tokens that don't expire
+    ///
+    /// Note that only JWTs carry an expiration time (the `exp` claim), whereas the Personal
+    /// Access Tokens (PATs) are opaque and don't expire; and that anonymous clients have no token.
+    pub fn token_expires_at(&self) -> Option<DateTime<Utc>> {
+        self.token_expires_at
+    }
+
+    /// Returns whether the token has already expired, so that long running processes can
+    /// proactively re-authenticate rather than failing mid-request; always `false` for PATs
+    pub fn is_token_expired(&self) -> bool {
+        self.token_expires_at
+            .is_some_and(|expires_at| expires_at <= self.clock.now())
+    }
+
+    /// Creates a new instance of DockerHubClient configured from the environment
+    ///
+    /// See `DockerHubClientBuilder::from_env` for the environment variables read, which allows
+    /// the same code to work on environments that front the Docker Hub with a mirror.
+    pub fn from_env() -> anyhow::Result<Self> {
+        Self::builder().from_env()?.build()
+    }
+
+    /// Returns the maximum number of retries for a failed request
+    pub fn max_retries(&self) -> usize {
+        self.max_retries
+    }
 
-        let client = Client::builder()
-            .default_headers(headers)
+    /// Returns the org or username used on the `_in` method variants, if set via the builder
+    pub fn default_namespace(&self) -> Option<&str> {
+        self.default_namespace.as_deref()
+    }
+
+    /// Returns the default namespace, failing if it wasn't set via the builder
+    fn require_default_namespace(&self) -> anyhow::Result<&str> {
+        self.default_namespace().context(
+            "no default namespace set, set it via `DockerHubClientBuilder::default_namespace`",
+        )
+    }
+
+    /// Creates a new DockerHubClientBuilder to configure the DockerHubClient before building it
+    pub fn builder() -> DockerHubClientBuilder {
+        DockerHubClientBuilder::default()
+    }
+
+    /// Creates a new client sharing the reqwest Client and the rest of the configuration of this
+    /// one i.e. base URL, clock, middleware, and concurrency, but that doesn't send the token
+    ///
+    /// Note that for the clients created via `from_client`, the token (if any) is carried by the
+    /// caller-provided Client itself, so it's still sent.
+    pub(crate) fn without_token(&self) -> DockerHubClient {
+        DockerHubClient {
+            username: OnceCell::new(),
+            authorization: None,
+            token_expires_at: None,
+            ..self.clone()
+        }
+    }
+}
+
+/// The policy to follow when the Docker Hub responds with a redirect
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RedirectPolicy {
+    /// Never follow the redirects, returning the redirect response as is, useful to e.g.
+    /// inspect the `Location` header of a 307 response pointing to a signed URL
+    None,
+
+    /// Follow up to the provided number of redirects
+    Limited(usize),
+}
+
+impl Default for RedirectPolicy {
+    /// Follows up to 10 redirects, same as the default for the reqwest Client
+    fn default() -> Self {
+        RedirectPolicy::Limited(10)
+    }
+}
+
+impl From<RedirectPolicy> for reqwest::redirect::Policy {
+    fn from(policy: RedirectPolicy) -> Self {
+        match policy {
+            RedirectPolicy::None => reqwest::redirect::Policy::none(),
+            RedirectPolicy::Limited(max) => reqwest::redirect::Policy::limited(max),
+        }
+    }
+}
+
+/// Builder to configure and create a DockerHubClient
+#[derive(Default)]
+pub struct DockerHubClientBuilder {
+    /// The token to authenticate the requests with, if any
+    token: Option<String>,
+
+    /// The policy to follow on redirects, defaults to following up to 10 redirects
+    redirect_policy: RedirectPolicy,
+
+    /// Whether to send a generated `X-Request-ID` header on every request
+    request_id_header: bool,
+
+    /// The hook applied to every outgoing request before sending it, if any
+    middleware: Option<Middleware>,
+
+    /// The hosts to resolve to a given socket address rather than via DNS
+    resolve_overrides: Vec<(String, SocketAddr)>,
+
+    /// The clock to use, defaults to the `SystemClock` if not provided
+    clock: Option<Arc<dyn Clock>>,
+
+    /// The base URL to send the requests to, defaults to `https://hub.docker.com` if not provided
+    base_url: Option<Url>,
+
+    /// Whether to verify the TLS certificates, defaults to `true` if not provided
+    tls_verify: Option<bool>,
+
+    /// The org or username to use on the `_in` method variants, if any
+    default_namespace: Option<String>,
+
+    /// The maximum number of concurrent requests, defaults to `MAX_CONCURRENT_REQUESTS`
+    max_concurrent_requests: Option<usize>,
+
+    /// The maximum number of retries for a failed request, defaults to the `RetryPolicy` one
+    max_retries: Option<usize>,
+}
+
+/// The environment variable holding the token to authenticate with
+pub const DOCKER_PAT_ENV: &str = "DOCKER_PAT";
+
+/// The environment variable holding the base URL of a mirror or pull-through cache fronting the
+/// Docker Hub
+pub const DOCKER_HUB_URL_ENV: &str = "DOCKER_HUB_URL";
+
+/// The environment variable that, when set to "0" or "false", disables the TLS verification
+pub const DOCKER_TLS_VERIFY_ENV: &str = "DOCKER_TLS_VERIFY";
+
+impl DockerHubClientBuilder {
+    /// Sets the token to authenticate with, which should have read access to the Docker Hub
+    pub fn token(mut self, token: &str) -> Self {
+        self.token = Some(token.to_string());
+        self
+    }
+
+    /// Sets the policy to follow when the Docker Hub responds with a redirect
+    pub fn redirect_policy(mut self, redirect_policy: RedirectPolicy) -> Self {
+        self.redirect_policy = redirect_policy;
+        self
+    }
+
+    /// Sets whether to send a newly generated `X-Request-ID` (UUID v4) header on every request
+    ///
+    /// The request id is regenerated per request, recorded in the tracing span of the request,
+    /// and included in the error context when the request fails; so that it can be logged and
+    /// provided to the Docker support to correlate with the Docker Hub diagnostics.
+    pub fn request_id_header(mut self, enabled: bool) -> Self {
+        self.request_id_header = enabled;
+        self
+    }
+
+    /// Sets a hook applied to every outgoing request right before sending it, including the
+    /// retries, so that the requests can be customized e.g. with extra headers or a signature
+    ///
+    /// This is a lighter alternative to `DockerHubClient::from_client` for when only per-request
+    /// tweaks are needed, as the rest of the configuration of the client is kept as is.
+    pub fn middleware<F>(mut self, middleware: F) -> Self
+    where
+        F: Fn(RequestBuilder) -> RequestBuilder + Send + Sync + 'static,
+    {
+        self.middleware = Some(Arc::new(middleware));
+        self
+    }
+
+    /// Resolves the provided `host` to the provided socket address rather than via DNS
+    ///
+    /// This is useful on split-horizon DNS setups, or to point the client to e.g. a local mock
+    /// serving the real hostname (i.e. `hub.docker.com`) via TLS, without editing `/etc/hosts`.
+    /// Note that the port within `addr` is ignored if the URL already specifies one.
+    pub fn resolve_to(mut self, host: &str, addr: SocketAddr) -> Self {
+        self.resolve_overrides.push((host.to_string(), addr));
+        self
+    }
+
+    /// Sets the clock used for the retry backoff and the token expiry checks, which is useful
+    /// to inject a `MockClock` on tests to control the time deterministically
+    pub fn clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Some(Arc::new(clock));
+        self
+    }
+
+    /// Sets the base URL to send the requests to e.g. a mirror fronting the Docker Hub
+    pub fn base_url(mut self, base_url: Url) -> Self {
+        self.base_url = Some(base_url);
+        self
+    }
+
+    /// Sets whether to verify the TLS certificates, only meant to be disabled for e.g. a
+    /// pull-through cache with a self-signed certificate
+    pub fn tls_verify(mut self, tls_verify: bool) -> Self {
+        self.tls_verify = Some(tls_verify);
+        self
+    }
+
+    /// Sets the org or username to use on the `_in` method variants e.g. `list_tags_in`, so
+    /// that the tools operating on a single org don't need to repeat it on every call
+    pub fn default_namespace(mut self, namespace: &str) -> Self {
+        self.default_namespace = Some(namespace.to_string());
+        self
+    }
+
+    /// Sets the maximum number of requests sent concurrently to the Docker Hub by the bulk
+    /// methods and the pagination, defaults to `MAX_CONCURRENT_REQUESTS`; where a lower value
+    /// helps to avoid hitting the rate limits, and zero is treated as one
+    pub fn max_concurrent_requests(mut self, max_concurrent_requests: usize) -> Self {
+        self.max_concurrent_requests = Some(max_concurrent_requests.max(1));
+        self
+    }
+
+    /// Sets the maximum number of retries for a request that failed with a transient error or
+    /// that was rate limited (429), in which case the retry waits until the time reported via
+    /// the `X-Retry-After` header; where zero disables the retries
+    pub fn max_retries(mut self, max_retries: usize) -> Self {
+        self.max_retries = Some(max_retries);
+        self
+    }
+
+    /// Reads the configuration that's not explicitly set yet from the environment
+    ///
+    /// The token is read from `DOCKER_PAT`, the base URL from `DOCKER_HUB_URL`, and the TLS
+    /// verification from `DOCKER_TLS_VERIFY`; where the latter is disabled when set to "0" or
+    /// "false". The settings explicitly set on the builder always take precedence over the
+    /// environment, regardless of whether those are set before or after calling this method,
+    /// and the unset or empty environment variables are ignored.
+    pub fn from_env(self) -> anyhow::Result<Self> {
+        self.with_env(|name| std::env::var(name).ok())
+    }
+
+    fn with_env<F>(mut self, var: F) -> anyhow::Result<Self>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let var = |name: &str| var(name).filter(|value| !value.trim().is_empty());
+
+        if self.token.is_none() {
+            self.token = var(DOCKER_PAT_ENV);
+        }
+        if self.base_url.is_none() {
+            self.base_url = var(DOCKER_HUB_URL_ENV)
+                .map(|base_url| {
+                    Url::parse(base_url.trim()).with_context(|| {
+                        format!("couldn't parse `{DOCKER_HUB_URL_ENV}` as an url: {base_url}")
+                    })
+                })
+                .transpose()?;
+        }
+        if self.tls_verify.is_none() {
+            self.tls_verify = var(DOCKER_TLS_VERIFY_ENV)
+                .map(|value| !matches!(value.trim().to_ascii_lowercase().as_str(), "0" | "false"));
+        }
+        Ok(self)
+    }
+
+    /// Builds the DockerHubClient with the provided configuration
+    pub fn build(self) -> anyhow::Result<DockerHubClient> {
+        let url = match self.base_url {
+            Some(base_url) => with_trailing_slash(base_url),
+            None => {
+                Url::parse("https://hub.docker.com").context("couldn't parse docker hub url")?
+            }
+        };
+
+        let token_expires_at = self.token.as_deref().and_then(jwt_expiration);
+
+        if let Some(token) = &self.token {
+            validate_token(token).context("the provided token is not valid")?;
+        }
+
+        let authorization = match self.token {
+            Some(token) => {
+                let mut authorization = header::HeaderValue::from_str(&format!("Bearer {}", token))
+                    .context("couldn't add authorization header with provided token")?;
+                authorization.set_sensitive(true);
+                Some(authorization)
+            }
+            None => None,
+        };
+
+        let mut builder = Client::builder()
+            .redirect(self.redirect_policy.into())
+            .danger_accept_invalid_certs(!self.tls_verify.unwrap_or(true));
+        for (host, addr) in &self.resolve_overrides {
+            builder = builder.resolve(host, *addr);
+        }
+        let client = builder
             .build()
             .context("couldn't build the reqwest client")?;
 
-        Ok(Self { client, url })
+        Ok(DockerHubClient {
+            client,
+            url,
+            categories: OnceCell::new(),
+            username: OnceCell::new(),
+            default_namespace: self.default_namespace,
+            max_concurrent_requests: self
+                .max_concurrent_requests
+                .unwrap_or(MAX_CONCURRENT_REQUESTS),
+            max_retries: self
+                .max_retries
+                .unwrap_or(RetryPolicy::default().max_retries),
+            authorization,
+            token_expires_at,
+            request_id_header: self.request_id_header,
+            middleware: self.middleware,
+            clock: self.clock.unwrap_or_else(|| Arc::new(SystemClock)),
+        })
+    }
+}
+
+/// Appends a trailing slash to the path of the provided base URL if missing, as otherwise its
+/// last path segment would be replaced rather than extended when joining e.g. `v2/categories`
+fn with_trailing_slash(mut url: Url) -> Url {
+    if !url.path().ends_with('/') {
+        let path = format!("{}/", url.path());
+        url.set_path(&path);
     }
+    url
+}
+
+/// Deserializes the provided JSON value into `T`, reporting the JSON path of the offending field
+/// (e.g. `results[3].images[0].last_pushed`) on failure so that API drift is easier to diagnose
+fn from_value<T>(value: Value) -> Result<T, HubError>
+where
+    T: for<'de> Deserialize<'de>,
+{
+    serde_path_to_error::deserialize::<_, T>(value).map_err(|e| HubError::Deserialization {
+        path: e.path().to_string(),
+        source: e.into_inner(),
+    })
 }
 
 pub async fn fetch<T>(
-    client: &Client,
+    client: &DockerHubClient,
     url: &Url,
     page: Option<usize>,
     page_size: Option<usize>,
-) -> anyhow::Result<T>
+) -> Result<T, HubError>
 where
     T: for<'de> Deserialize<'de> + Send + 'static,
 {
+    let value = fetch_value(client, url, page, page_size).await?;
+    from_value::<T>(value)
+}
+
+/// Same as `fetch` but also returns the original JSON payload alongside the parsed `T`
+///
+/// This is useful for debugging, as well as to access fields that the structs within this crate
+/// don't model yet, without requiring a new release every time the Docker Hub adds a new field.
+pub async fn fetch_raw<T>(
+    client: &DockerHubClient,
+    url: &Url,
+    page: Option<usize>,
+    page_size: Option<usize>,
+) -> Result<(T, Value), HubError>
+where
+    T: for<'de> Deserialize<'de> + Send + 'static,
+{
+    let value = fetch_value(client, url, page, page_size).await?;
+    let parsed = from_value::<T>(value.clone())?;
+    Ok((parsed, value))
+}
+
+async fn fetch_value(
+    client: &DockerHubClient,
+    url: &Url,
+    page: Option<usize>,
+    page_size: Option<usize>,
+) -> Result<Value, HubError> {
     let query = if let (Some(p), Some(ps)) = (page, page_size) {
-        client
-            .get(url.clone())
-            .query(&[("page", p), ("page_size", ps)])
+        client.client.get(url.clone()).query(&page_query(p, ps))
     } else {
-        client.get(url.clone())
+        client.client.get(url.clone())
     };
 
-    match query.send().await {
-        Ok(response) => {
-            match response.status() {
-                // 429
-                reqwest::StatusCode::TOO_MANY_REQUESTS => {
-                    // The Docker Hub API is limited on the amount of requests you can perform per minute against it.
-                    // If you have hit the limit, you will receive a response status of 429 and the X-Retry-After header in the response.
-                    // The X-Retry-After header is a unix timestamp of when you can call the API again.
-                    if let Some(retry_after) = response.headers().get("X-Retry-After") {
-                        anyhow::bail!(
-                            "available requests exhausted, please try again after {}",
-                            retry_after.to_str().unwrap()
-                        )
-                    } else {
-                        anyhow::bail!("too many requests sent to the docker hub")
-                    }
-                }
-                // 404
-                reqwest::StatusCode::NOT_FOUND => {
-                    anyhow::bail!("{url} not found")
-                }
-                // 403
-                reqwest::StatusCode::UNAUTHORIZED => {
-                    anyhow::bail!("provided client is not authorized")
-                }
-                // 200 or 201
-                reqwest::StatusCode::OK | reqwest::StatusCode::CREATED => {
-                    match response.json::<Value>().await {
-                        Ok(out) => serde_json::from_value::<T>(out)
-                            .context("parsing the output json into an `T` struct failed"),
-                        Err(e) => anyhow::bail!("failed with error {e}"),
-                    }
-                }
-                _ => anyhow::bail!("request failed with status code {}", response.status()),
+    send_request(client, url, query).await
+}
+
+/// Same as `fetch` but sending the request with the provided `options` e.g. to disable the
+/// retries, rather than with the default ones
+pub(crate) async fn fetch_with_options<T>(
+    client: &DockerHubClient,
+    url: &Url,
+    options: &SendOptions,
+) -> Result<T, HubError>
+where
+    T: for<'de> Deserialize<'de> + Send + 'static,
+{
+    let request = client.client.get(url.clone());
+    let value = send_request_with_options(client, url, request, options).await?;
+    from_value::<T>(value)
+}
+
+/// Returns the query values for the provided `page` and `page_size`, clamping the latter to
+/// the maximum page size allowed by the Docker Hub
+fn page_query(page: usize, page_size: usize) -> [(&'static str, usize); 2] {
+    [("page", page), ("page_size", page_size.min(MAX_PAGE_SIZE))]
+}
+
+/// Options to customize how a request is sent in `send_with_options`
+#[derive(Clone, Debug, Default)]
+pub struct SendOptions {
+    /// Whether the request is safe to retry i.e. sending it more than once has the same effect
+    /// as sending it once, e.g. a `PATCH` setting a field to a given value. Only `GET` and `HEAD`
+    /// requests are retried by default, so that e.g. a `POST` or a `DELETE` that failed on a
+    /// transport error after reaching the Docker Hub isn't sent twice.
+    pub idempotent: bool,
+
+    /// Overrides the maximum number of retries of the client if any, e.g. `Some(0)` to send the
+    /// request only once when the caller already polls on its own
+    pub max_retries: Option<usize>,
+}
+
+/// Sends a request with the provided `method` and JSON `body` to the Docker Hub
+///
+/// This function is the counterpart of `fetch` for the endpoints that modify resources on the
+/// Docker Hub e.g. `PATCH` or `POST` requests, sharing the same response handling. Note that
+/// the request is not retried unless it's a `GET` or a `HEAD`, see `send_with_options` to opt
+/// in to the retries for the idempotent requests.
+pub async fn send<B, T>(
+    client: &DockerHubClient,
+    method: Method,
+    url: &Url,
+    body: &B,
+) -> Result<T, HubError>
+where
+    B: Serialize + ?Sized,
+    T: for<'de> Deserialize<'de> + Send + 'static,
+{
+    send_with_options(client, method, url, body, &SendOptions::default()).await
+}
+
+/// Same as `send` but with the provided `options` e.g. to retry an idempotent `PATCH` request
+pub async fn send_with_options<B, T>(
+    client: &DockerHubClient,
+    method: Method,
+    url: &Url,
+    body: &B,
+    options: &SendOptions,
+) -> Result<T, HubError>
+where
+    B: Serialize + ?Sized,
+    T: for<'de> Deserialize<'de> + Send + 'static,
+{
+    let request = client.client.request(method, url.clone()).json(body);
+    let value = send_request_with_options(client, url, request, options).await?;
+    from_value::<T>(value)
+}
+
+/// The request header to send the generated request id in, when enabled via the builder
+const REQUEST_ID_HEADER: &str = "X-Request-ID";
+
+/// The response headers that the Docker Hub may send and that are useful to diagnose failures,
+/// e.g. the request id to include on bug reports sent to Docker
+const DIAGNOSTIC_HEADERS: [&str; 4] = ["x-request-id", "x-trace-id", "x-served-by", "x-source"];
+
+/// Formats the diagnostic headers (if any) in the provided headers as `name: value` pairs
+fn diagnostic_headers(headers: &header::HeaderMap) -> String {
+    DIAGNOSTIC_HEADERS
+        .iter()
+        .filter_map(|name| {
+            headers
+                .get(*name)
+                .and_then(|value| value.to_str().ok())
+                .map(|value| format!("{name}: {value}"))
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Sends the provided request, retrying it on transient failures as per the default policy
+async fn send_request(
+    client: &DockerHubClient,
+    url: &Url,
+    request: RequestBuilder,
+) -> Result<Value, HubError> {
+    send_request_with_options(client, url, request, &SendOptions::default()).await
+}
+
+async fn send_request_with_options(
+    client: &DockerHubClient,
+    url: &Url,
+    request: RequestBuilder,
+    options: &SendOptions,
+) -> Result<Value, HubError> {
+    let method = request
+        .try_clone()
+        .and_then(|request| request.build().ok())
+        .map(|request| request.method().clone());
+    let policy = retry_policy(
+        method.as_ref(),
+        options.idempotent,
+        options.max_retries.unwrap_or(client.max_retries),
+    );
+
+    retry_with(client.clock.as_ref(), &policy, || {
+        let request = request.try_clone();
+        async move {
+            match request {
+                Some(request) => send_attempt(client, url, request).await,
+                None => Err(RetryError::Fatal(HubError::Other(anyhow::anyhow!(
+                    "couldn't clone the request to send it"
+                )))),
             }
         }
-        Err(e) => anyhow::bail!("failed with error {e}"),
+    })
+    .await
+    .map_err(|failure| {
+        if failure.exhausted {
+            failure.error.with_retries(failure.retries)
+        } else {
+            failure.error
+        }
+    })
+}
+
+/// Returns the retry policy for a request with the provided `method`, where only the `GET` and
+/// `HEAD` requests, or the ones explicitly flagged as `idempotent`, are retried up to
+/// `max_retries` times
+fn retry_policy(method: Option<&Method>, idempotent: bool, max_retries: usize) -> RetryPolicy {
+    let retryable = idempotent || matches!(method, Some(&Method::GET) | Some(&Method::HEAD));
+    RetryPolicy {
+        max_retries: if retryable { max_retries } else { 0 },
+        ..Default::default()
     }
 }
 
-pub async fn fetch_with_pagination<T>(client: &Client, url: &Url) -> anyhow::Result<Vec<T>>
+#[tracing::instrument(
+    skip(client, request),
+    fields(
+        url = %url,
+        request_id = tracing::field::Empty,
+        status = tracing::field::Empty,
+        diagnostics = tracing::field::Empty
+    )
+)]
+async fn send_attempt(
+    client: &DockerHubClient,
+    url: &Url,
+    request: RequestBuilder,
+) -> Result<Value, RetryError<HubError>> {
+    let span = tracing::Span::current();
+
+    // A new request id is generated for every request sent, including the retries, so that
+    // each of those can be correlated with the Docker Hub diagnostics
+    let request = match &client.authorization {
+        Some(authorization) => request.header(header::AUTHORIZATION, authorization.clone()),
+        None => request,
+    };
+
+    let request_id = client.request_id_header.then(|| Uuid::new_v4().to_string());
+    let request = match &request_id {
+        Some(request_id) => {
+            span.record("request_id", request_id.as_str());
+            request.header(REQUEST_ID_HEADER, request_id)
+        }
+        None => request,
+    };
+    let request = match &client.middleware {
+        Some(middleware) => middleware(request),
+        None => request,
+    };
+
+    let response = match request.send().await {
+        Ok(response) => response,
+        // Connection errors and timeouts are transient, so those are worth retrying
+        Err(e) if e.is_connect() || e.is_timeout() => {
+            return Err(RetryError::retryable(
+                HubError::Request(e).with_details(request_id, None),
+            ))
+        }
+        Err(e) => {
+            return Err(RetryError::Fatal(
+                HubError::Request(e).with_details(request_id, None),
+            ))
+        }
+    };
+
+    let diagnostics = diagnostic_headers(response.headers());
+    span.record("status", response.status().as_u16());
+    span.record("diagnostics", diagnostics.as_str());
+
+    let diagnostics = (!diagnostics.is_empty()).then_some(diagnostics);
+    let with_details = |e: HubError| e.with_details(request_id.clone(), diagnostics.clone());
+
+    match response.status() {
+        // 429, retried once the time reported via the `X-Retry-After` header is reached, if any,
+        // unless that's further away than the maximum backoff in which case it's returned
+        reqwest::StatusCode::TOO_MANY_REQUESTS => {
+            let error = HubError::rate_limited(
+                url,
+                response
+                    .headers()
+                    .get("X-Retry-After")
+                    .and_then(|value| value.to_str().ok()),
+            );
+            let retry_after = match &error {
+                HubError::RateLimited {
+                    retry_after: Some(retry_after),
+                    ..
+                } => Some(
+                    (*retry_after - client.clock.now())
+                        .to_std()
+                        .unwrap_or_default(),
+                ),
+                _ => None,
+            };
+            Err(RetryError::Retryable {
+                error: with_details(error),
+                retry_after,
+            })
+        }
+        // 502, 503, or 504
+        reqwest::StatusCode::BAD_GATEWAY
+        | reqwest::StatusCode::SERVICE_UNAVAILABLE
+        | reqwest::StatusCode::GATEWAY_TIMEOUT => {
+            let status = response.status();
+            let error = HubError::Http {
+                status,
+                url: url.clone(),
+                source: ensure_json(response).await.err().map(Box::new),
+            };
+            Err(RetryError::retryable(with_details(error)))
+        }
+        _ => handle_response(url, response)
+            .await
+            .map_err(|e| RetryError::Fatal(with_details(e))),
+    }
+}
+
+async fn handle_response(url: &Url, response: Response) -> Result<Value, HubError> {
+    match response.status() {
+        // 429
+        reqwest::StatusCode::TOO_MANY_REQUESTS => {
+            // The Docker Hub API is limited on the amount of requests you can perform per minute against it.
+            // If you have hit the limit, you will receive a response status of 429 and the X-Retry-After header in the response.
+            // The X-Retry-After header is a unix timestamp of when you can call the API again.
+            let retry_after = response
+                .headers()
+                .get("X-Retry-After")
+                .and_then(|value| value.to_str().ok());
+            Err(HubError::rate_limited(url, retry_after))
+        }
+        // 404
+        reqwest::StatusCode::NOT_FOUND => Err(HubError::NotFound(url.clone())),
+        // 401
+        reqwest::StatusCode::UNAUTHORIZED => Err(HubError::Unauthorized(url.clone())),
+        // 403
+        reqwest::StatusCode::FORBIDDEN => Err(HubError::Forbidden(url.clone())),
+        // 200 or 201
+        reqwest::StatusCode::OK | reqwest::StatusCode::CREATED => {
+            let response = ensure_json(response).await?;
+            Ok(response.json::<Value>().await?)
+        }
+        // 204
+        reqwest::StatusCode::NO_CONTENT => Ok(Value::Null),
+        _ => Err(HubError::Http {
+            status: response.status(),
+            url: url.clone(),
+            source: None,
+        }),
+    }
+}
+
+/// Returns the provided response back if its `Content-Type` is JSON (or missing), otherwise reads
+/// its body and returns a `HubError::UnexpectedContentType` with a snippet of it
+///
+/// This turns the HTML error pages sent by proxies or captive portals into a clear error, rather
+/// than a confusing JSON parsing error.
+async fn ensure_json(response: Response) -> Result<Response, HubError> {
+    let content_type = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(String::from);
+
+    match content_type {
+        Some(content_type) if !is_json_content_type(&content_type) => {
+            let body = response.text().await.unwrap_or_default();
+            Err(HubError::unexpected_content_type(Some(content_type), &body))
+        }
+        _ => Ok(response),
+    }
+}
+
+/// Options to customize how the paginated results are fetched in `fetch_with_pagination_with_options`
+#[derive(Clone, Debug, Default)]
+pub struct PaginationOptions {
+    /// Whether to abort the remaining page requests and return as soon as any page fails,
+    /// rather than waiting for all the page requests to complete (the default); useful for
+    /// interactive tools where failing early is preferable.
+    pub fail_fast: bool,
+
+    /// The maximum number of items to collect, if any; in which case the pages are fetched one
+    /// after the other and the pagination stops as soon as that many items are collected,
+    /// rather than fetching the full set.
+    pub max_items: Option<usize>,
+}
+
+pub async fn fetch_with_pagination<T>(
+    client: &DockerHubClient,
+    url: &Url,
+) -> Result<Vec<T>, HubError>
+where
+    T: for<'de> Deserialize<'de> + Send + 'static,
+{
+    fetch_with_pagination_with_options(client, url, &PaginationOptions::default()).await
+}
+
+pub async fn fetch_with_pagination_with_options<T>(
+    client: &DockerHubClient,
+    url: &Url,
+    options: &PaginationOptions,
+) -> Result<Vec<T>, HubError>
 where
     T: for<'de> Deserialize<'de> + Send + 'static,
 {
-    let result = fetch::<ApiResult<T>>(client, url, Some(1), Some(10)).await?;
+    if let Some(max_items) = options.max_items {
+        let mut results = Vec::new();
+        if max_items > 0 {
+            walk_pages::<T, _>(client, url, |page| {
+                results.extend(page);
+                if results.len() >= max_items {
+                    ControlFlow::Break(())
+                } else {
+                    ControlFlow::Continue(())
+                }
+            })
+            .await?;
+            results.truncate(max_items);
+        }
+        return Ok(results);
+    }
+
+    let client = client.clone();
+    let url = url.clone();
+    paginate(
+        options,
+        client.max_concurrent_requests,
+        move |page, page_size| {
+            let client = client.clone();
+            let url = url.clone();
+            async move { fetch::<ApiResult<T>>(&client, &url, Some(page), Some(page_size)).await }
+        },
+    )
+    .await
+}
+
+/// The page size requested for the first page in `paginate`
+const FIRST_PAGE_SIZE: usize = 10;
+
+/// Fetches all the pages via the provided `fetch_page` closure, which receives the `page` and
+/// `page_size` to fetch, so that the pagination itself can be tested against a mocked paginator
+///
+/// The first page is fetched to know the total count of results, and then the rest of the pages
+/// are fetched concurrently with at most `max_concurrent_requests` requests in flight, collecting
+/// the results in the same order as the pages.
+async fn paginate<T, F, Fut>(
+    options: &PaginationOptions,
+    max_concurrent_requests: usize,
+    fetch_page: F,
+) -> Result<Vec<T>, HubError>
+where
+    T: Send + 'static,
+    F: Fn(usize, usize) -> Fut,
+    Fut: Future<Output = Result<ApiResult<T>, HubError>> + Send + 'static,
+{
+    let result = fetch_page(1, FIRST_PAGE_SIZE).await?;
 
-    if let Some(_) = result.next {
-        let page_size = result.results.len();
-        let pages = (result.count + page_size - 1) / page_size;
+    if result.next.is_some() {
+        // The effective page size is derived from the results rather than from the requested
+        // page size, as the Docker Hub may clamp the latter; note that the first page can only
+        // hold fewer results than requested when clamped, as otherwise there'd be no next page.
+        // The filtered queries may still send an empty first page with a next page, in which
+        // case the requested page size is used instead
+        let page_size = match result.results.len() {
+            0 => FIRST_PAGE_SIZE,
+            page_size => page_size,
+        };
+        let pages = result.count.div_ceil(page_size);
 
-        // TODO: avoid spawning a bunch of tasks
+        // A task is spawned per page, but each of those waits for a permit before sending its
+        // request, so that the requests in flight are bounded e.g. to avoid hitting a 429
+        let semaphore = Arc::new(Semaphore::new(max_concurrent_requests.max(1)));
         let mut tasks = Vec::new();
-        for page in 2..pages {
-            let new_url = url.clone();
-            let new_client = client.clone();
+        for page in 2..=pages {
+            let semaphore = semaphore.clone();
+            let future = fetch_page(page, page_size);
             tasks.push(tokio::spawn(async move {
-                fetch::<ApiResult<T>>(&new_client, &new_url, Some(page), Some(page_size)).await
+                let _permit = semaphore
+                    .acquire_owned()
+                    .await
+                    .context("the concurrency limit semaphore was closed")
+                    .map_err(HubError::Other)?;
+                future.await
             }));
         }
 
         let mut results = result.results;
 
-        let futures = join_all(tasks).await;
-        for future in futures {
-            match future {
-                Ok(Ok(result)) => {
-                    results.extend(result.results);
-                }
-                Ok(Err(e)) => {
-                    anyhow::bail!("failed to fetch: {:?}", e);
-                }
-                Err(e) => {
-                    anyhow::bail!("failed capturing the task future: {:?}", e);
+        if options.fail_fast {
+            let abort_handles = tasks
+                .iter()
+                .map(|task| task.abort_handle())
+                .collect::<Vec<_>>();
+
+            let mut futures = tasks
+                .into_iter()
+                .enumerate()
+                .map(|(index, task)| async move { (index, task.await) })
+                .collect::<FuturesUnordered<_>>();
+
+            let mut pages = Vec::new();
+            while let Some((index, future)) = futures.next().await {
+                match page_results(future) {
+                    Ok(page) => pages.push((index, page)),
+                    Err(e) => {
+                        abort_handles.iter().for_each(AbortHandle::abort);
+                        return Err(e);
+                    }
                 }
             }
+
+            // The pages may complete in any order, so those are sorted to keep the same order
+            // as when collecting all the results
+            pages.sort_by_key(|(index, _)| *index);
+            for (_, page) in pages {
+                results.extend(page);
+            }
+        } else {
+            let futures = join_all(tasks).await;
+            for future in futures {
+                results.extend(page_results(future)?);
+            }
         }
         Ok(results)
     } else {
         Ok(result.results)
     }
 }
+
+/// Counts the paginated results by probing the pages concurrently via the provided `fetch_page`
+/// closure, for the endpoints that don't report the `count`
+///
+/// The pages are probed in batches of up to `max_concurrent_requests` pages of `MAX_PAGE_SIZE`
+/// results each, until the last page is found i.e. the first page with either no next page or
+/// fewer results than the page size; so that only the results of the last page need counting.
+pub(crate) async fn probe_count<T, F, Fut>(
+    max_concurrent_requests: usize,
+    fetch_page: F,
+) -> Result<usize, HubError>
+where
+    F: Fn(usize, usize) -> Fut,
+    Fut: Future<Output = Result<ApiResult<T>, HubError>>,
+{
+    let mut full_pages = 0;
+    loop {
+        let first = full_pages + 1;
+        let pages = join_all(
+            (first..first + max_concurrent_requests).map(|page| fetch_page(page, MAX_PAGE_SIZE)),
+        )
+        .await;
+
+        // The pages are checked in order, so that the errors of the pages past the last one
+        // (e.g. a 404) are ignored
+        for page in pages {
+            let page = page?;
+            if page.next.is_none() || page.results.len() < MAX_PAGE_SIZE {
+                return Ok(full_pages * MAX_PAGE_SIZE + page.results.len());
+            }
+            full_pages += 1;
+        }
+    }
+}
+
+/// Fetches a single page of results from a cursor i.e. the `next` URL of a previous page
+///
+/// This function allows resuming an interrupted pagination, since the `ApiResult::next` URL
+/// contains the query values for the `page` and `page_size` to fetch next; so that very large
+/// and long running jobs can persist it (e.g. to disk) after processing each page, and resume
+/// from the last persisted cursor after a crash rather than starting over. Note that the cursors
+/// pointing to a different origin than the client URL are rejected, so that the token is never
+/// sent elsewhere e.g. when resuming from a tampered cursor:
+///
+/// ```rust,no_run
+/// use hub_tool::{fetch_page_from_cursor, tags::Tag, DockerHubClient};
+///
+/// # async fn run(client: DockerHubClient, cursor: String) -> anyhow::Result<()> {
+/// let mut cursor = Some(cursor);
+/// while let Some(url) = cursor {
+///     let page = fetch_page_from_cursor::<Tag>(&client, &url).await?;
+///     // ... process `page.results()` and persist `page.next()` somewhere
+///     cursor = page.next().map(String::from);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub async fn fetch_page_from_cursor<T>(
+    client: &DockerHubClient,
+    cursor_url: &str,
+) -> Result<ApiResult<T>, HubError>
+where
+    T: for<'de> Deserialize<'de> + Send + 'static,
+{
+    let url = Url::parse(cursor_url).context("couldn't parse the provided cursor url")?;
+    if url.origin() != client.url.origin() {
+        return Err(anyhow::anyhow!(
+            "the provided cursor url {url} doesn't match the origin of {}",
+            client.url
+        )
+        .into());
+    }
+    fetch::<ApiResult<T>>(client, &url, None, None).await
+}
+
+/// Fetches all the paginated results by following the `next` URL of each page until there are
+/// no more pages
+///
+/// This is slower than `fetch_with_pagination` as the pages are fetched one after the other, but
+/// it doesn't rely on the page count computed from the `count` and the size of the first page;
+/// so it remains correct even if the Docker Hub changes the page size mid-way, or returns a
+/// non-uniform last page.
+pub async fn fetch_all<T>(client: &DockerHubClient, url: &Url) -> Result<Vec<T>, HubError>
+where
+    T: for<'de> Deserialize<'de> + Send + 'static,
+{
+    let mut results = Vec::new();
+    walk_pages::<T, _>(client, url, |page| {
+        results.extend(page);
+        ControlFlow::Continue(())
+    })
+    .await?;
+    Ok(results)
+}
+
+/// Fetches the paginated results by following the `next` URL of each page, sending at most
+/// `request_budget` page requests
+///
+/// This bounds the cost of a listing regardless of the number of results, e.g. to cap the
+/// requests counted against the rate limits deterministically. Once the budget is exhausted
+/// the results collected so far are returned along with a `Warning::BudgetExceeded`, rather
+/// than failing or fetching the rest of the pages. Note that only the pages are counted, so
+/// the retries of a page (if any) don't consume the budget.
+pub async fn fetch_with_budget<T>(
+    client: &DockerHubClient,
+    url: &Url,
+    request_budget: usize,
+) -> Result<Outcome<Vec<T>>, HubError>
+where
+    T: for<'de> Deserialize<'de> + Send + 'static,
+{
+    let mut first = url.clone();
+    first
+        .query_pairs_mut()
+        .append_pair("page", "1")
+        .append_pair("page_size", &MAX_PAGE_SIZE.to_string());
+
+    collect_with_budget(first.to_string(), request_budget, |cursor| async move {
+        fetch_page_from_cursor::<T>(client, &cursor).await
+    })
+    .await
+}
+
+/// Collects the pages fetched via the provided `fetch_page` closure, which receives the URL of
+/// the page to fetch, starting from `first` and following the `next` URL of each page until
+/// either there are no more pages or `request_budget` pages have been fetched
+async fn collect_with_budget<T, F, Fut>(
+    first: String,
+    request_budget: usize,
+    fetch_page: F,
+) -> Result<Outcome<Vec<T>>, HubError>
+where
+    F: Fn(String) -> Fut,
+    Fut: Future<Output = Result<ApiResult<T>, HubError>>,
+{
+    let mut outcome = Outcome::<Vec<T>>::default();
+    let mut cursor = Some(first);
+    let mut requests = 0;
+    while let Some(next) = cursor.take() {
+        if requests >= request_budget {
+            outcome.warnings.push(Warning::BudgetExceeded {
+                request_budget,
+                next,
+            });
+            break;
+        }
+        let page = fetch_page(next).await?;
+        requests += 1;
+        outcome.value.extend(page.results);
+        cursor = page.next;
+    }
+    Ok(outcome)
+}
+
+/// Streams the paginated results, yielding the items page by page as each page arrives
+///
+/// The pages are fetched one after the other by following the `next` URL of each page, and
+/// only when the items of the previous page have been consumed; so that the memory usage stays
+/// flat, and that the stream can be dropped early e.g. once a given item is found, without
+/// fetching the rest of the pages:
+///
+/// ```rust,no_run
+/// use futures::StreamExt;
+/// use hub_tool::{fetch_stream, tags::Tag, DockerHubClient};
+/// use url::Url;
+///
+/// # async fn run(client: DockerHubClient, url: Url) -> anyhow::Result<()> {
+/// let mut stream = Box::pin(fetch_stream::<Tag>(&client, &url));
+/// while let Some(tag) = stream.next().await {
+///     if tag?.name() == "latest" {
+///         break;
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub fn fetch_stream<'a, T>(
+    client: &'a DockerHubClient,
+    url: &Url,
+) -> impl Stream<Item = Result<T, HubError>> + 'a
+where
+    T: for<'de> Deserialize<'de> + Send + 'static,
+{
+    enum Cursor {
+        First(Url),
+        Next(String),
+    }
+
+    stream::try_unfold(Some(Cursor::First(url.clone())), move |cursor| async move {
+        let page = match cursor {
+            Some(Cursor::First(url)) => {
+                fetch::<ApiResult<T>>(client, &url, Some(1), Some(MAX_PAGE_SIZE)).await?
+            }
+            Some(Cursor::Next(next)) => fetch_page_from_cursor::<T>(client, &next).await?,
+            None => return Ok(None),
+        };
+        let next = page.next.map(Cursor::Next);
+        Ok::<_, HubError>(Some((page.results, next)))
+    })
+    .map_ok(|results| stream::iter(results.into_iter().map(Ok)))
+    .try_flatten()
+}
+
+/// Walks through the pages sequentially by following the `next` URL of each page, calling `f`
+/// with the results of each page until either `f` breaks or there are no more pages
+///
+/// Same as `fetch_all` but allows stopping the pagination early e.g. once a matching result has
+/// been found, without fetching the rest of the pages.
+pub(crate) async fn walk_pages<T, F>(
+    client: &DockerHubClient,
+    url: &Url,
+    mut f: F,
+) -> Result<(), HubError>
+where
+    T: for<'de> Deserialize<'de> + Send + 'static,
+    F: FnMut(Vec<T>) -> ControlFlow<()>,
+{
+    let mut page = fetch::<ApiResult<T>>(client, url, Some(1), Some(MAX_PAGE_SIZE)).await?;
+    loop {
+        let next = page.next.take();
+        if f(page.results).is_break() {
+            return Ok(());
+        }
+        match next {
+            Some(next) => page = fetch_page_from_cursor::<T>(client, &next).await?,
+            None => return Ok(()),
+        }
+    }
+}
+
+/// Unwraps the results of a spawned page request in `fetch_with_pagination_with_options`
+fn page_results<T>(
+    future: Result<Result<ApiResult<T>, HubError>, JoinError>,
+) -> Result<Vec<T>, HubError> {
+    match future {
+        Ok(Ok(result)) => Ok(result.results),
+        Ok(Err(e)) => Err(e),
+        Err(e) => Err(anyhow::anyhow!("failed capturing the task future: {:?}", e).into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    use crate::tags::Tag;
+
+    #[test]
+    fn test_is_token_expired() {
+        use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+        use base64::Engine;
+
+        use crate::clock::MockClock;
+
+        let header = URL_SAFE_NO_PAD.encode(r#"{"alg":"RS256","typ":"JWT"}"#);
+        let payload = URL_SAFE_NO_PAD.encode(r#"{"sub":"alvarobartt","exp":1741600000}"#);
+        let token = format!("{header}.{payload}.signature");
+
+        let clock = MockClock::new(DateTime::from_timestamp(1741599000, 0).unwrap());
+        let client = DockerHubClient::builder()
+            .token(&token)
+            .clock(clock.clone())
+            .build()
+            .unwrap();
+
+        assert!(!client.is_token_expired());
+        clock.advance(std::time::Duration::from_secs(1000));
+        assert!(client.is_token_expired());
+
+        let client = DockerHubClient::builder()
+            .token("dckr_pat_abcdefghijklmnopqrstuvwxyz")
+            .clock(clock)
+            .build()
+            .unwrap();
+        assert_eq!(client.token_expires_at(), None);
+        assert!(!client.is_token_expired());
+    }
+
+    #[test]
+    fn test_builder_with_env() {
+        let env = |name: &str| match name {
+            DOCKER_PAT_ENV => Some("dckr_pat_env".to_string()),
+            DOCKER_HUB_URL_ENV => Some("https://mirror.example.com".to_string()),
+            DOCKER_TLS_VERIFY_ENV => Some("0".to_string()),
+            _ => None,
+        };
+
+        let builder = DockerHubClient::builder().with_env(env).unwrap();
+        assert_eq!(builder.token.as_deref(), Some("dckr_pat_env"));
+        assert_eq!(
+            builder.base_url.as_ref().map(Url::as_str),
+            Some("https://mirror.example.com/")
+        );
+        assert_eq!(builder.tls_verify, Some(false));
+
+        // The explicit settings take precedence over the environment
+        let builder = DockerHubClient::builder()
+            .token("dckr_pat_explicit")
+            .tls_verify(true)
+            .with_env(env)
+            .unwrap();
+        assert_eq!(builder.token.as_deref(), Some("dckr_pat_explicit"));
+        assert_eq!(builder.tls_verify, Some(true));
+
+        // The empty environment variables are ignored
+        let builder = DockerHubClient::builder()
+            .with_env(|_| Some(String::new()))
+            .unwrap();
+        assert!(builder.token.is_none());
+        assert!(builder.base_url.is_none());
+        assert!(builder.tls_verify.is_none());
+    }
+
+    #[test]
+    fn test_retry_policy_only_retries_idempotent_requests() {
+        assert_eq!(retry_policy(Some(&Method::GET), false, 3).max_retries, 3);
+        assert_eq!(retry_policy(Some(&Method::HEAD), false, 3).max_retries, 3);
+        assert_eq!(retry_policy(Some(&Method::POST), false, 3).max_retries, 0);
+        assert_eq!(retry_policy(Some(&Method::DELETE), false, 3).max_retries, 0);
+        assert_eq!(retry_policy(Some(&Method::PATCH), true, 3).max_retries, 3);
+        assert_eq!(retry_policy(None, false, 3).max_retries, 0);
+    }
+
+    #[test]
+    fn test_api_result_without_count() {
+        let value = json!({
+          "next": null,
+          "results": [
+            { "name": "Machine Learning & AI", "slug": "machine-learning-and-ai" },
+            { "name": "Developer Tools", "slug": "developer-tools" }
+          ]
+        });
+
+        let result = from_value::<ApiResult<Category>>(value).unwrap();
+        assert_eq!(result.count(), 2);
+        assert_eq!(result.next(), None);
+        assert_eq!(result.previous(), None);
+    }
+
+    #[test]
+    fn test_with_base_url() {
+        for base_url in [
+            "https://registry.example.com/prefix",
+            "https://registry.example.com/prefix/",
+        ] {
+            let client = DockerHubClient::with_base_url("dckr_pat_***", base_url).unwrap();
+            assert_eq!(
+                client.url.join("v2/categories").unwrap().as_str(),
+                "https://registry.example.com/prefix/v2/categories"
+            );
+        }
+
+        let client =
+            DockerHubClient::with_base_url("dckr_pat_***", "https://hub.docker.com").unwrap();
+        assert_eq!(
+            client.url.join("v2/categories").unwrap().as_str(),
+            "https://hub.docker.com/v2/categories"
+        );
+
+        assert!(DockerHubClient::with_base_url("dckr_pat_***", "not a url").is_err());
+    }
+
+    #[test]
+    fn test_api_result_results_shapes() {
+        let value = json!({
+          "count": 1,
+          "next": null,
+          "previous": null,
+          "results": [{ "name": "Developer Tools", "slug": "developer-tools" }]
+        });
+        let result = from_value::<ApiResult<Category>>(value).unwrap();
+        assert_eq!(result.results().len(), 1);
+
+        let value = json!({
+          "count": 1,
+          "next": null,
+          "previous": null,
+          "results": { "name": "Developer Tools", "slug": "developer-tools" }
+        });
+        let result = from_value::<ApiResult<Category>>(value).unwrap();
+        assert_eq!(result.results().len(), 1);
+        assert_eq!(result.results()[0].slug(), "developer-tools");
+    }
+
+    /// Returns a mocked paginator over `count` results i.e. the numbers from zero to `count`
+    fn mock_paginator(
+        count: usize,
+    ) -> impl Fn(usize, usize) -> futures::future::Ready<Result<ApiResult<usize>, HubError>> {
+        move |page, page_size| {
+            let start = (page - 1) * page_size;
+            let end = (start + page_size).min(count);
+            futures::future::ready(Ok(ApiResult {
+                count,
+                next: (end < count).then(|| format!("?page={}", page + 1)),
+                previous: (page > 1).then(|| format!("?page={}", page - 1)),
+                results: (start.min(end)..end).collect(),
+            }))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_paginate_fetches_all_pages() {
+        for count in [0, 5, 10, 25, 30, 31] {
+            for fail_fast in [false, true] {
+                let options = PaginationOptions {
+                    fail_fast,
+                    ..Default::default()
+                };
+                let results = paginate(&options, MAX_CONCURRENT_REQUESTS, mock_paginator(count))
+                    .await
+                    .unwrap();
+                assert_eq!(results, (0..count).collect::<Vec<_>>());
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_paginate_bounds_concurrency() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let max_in_flight = Arc::new(AtomicUsize::new(0));
+        let paginator = mock_paginator(500);
+
+        let results = paginate(&PaginationOptions::default(), 3, |page, page_size| {
+            let in_flight = in_flight.clone();
+            let max_in_flight = max_in_flight.clone();
+            let page = paginator(page, page_size);
+            async move {
+                let current = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                max_in_flight.fetch_max(current, Ordering::SeqCst);
+                tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+                in_flight.fetch_sub(1, Ordering::SeqCst);
+                page.await
+            }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(results.len(), 500);
+        assert!(max_in_flight.load(Ordering::SeqCst) <= 3);
+    }
+
+    #[tokio::test]
+    async fn test_probe_count() {
+        for count in [0, 1, 100, 250, 800, 801, 2345] {
+            for max_concurrent_requests in [1, 3, MAX_CONCURRENT_REQUESTS] {
+                assert_eq!(
+                    probe_count(max_concurrent_requests, mock_paginator(count))
+                        .await
+                        .unwrap(),
+                    count
+                );
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_collect_with_budget() {
+        let paginator = mock_paginator(25);
+        let fetch_page = |cursor: String| {
+            let page = cursor.trim_start_matches("?page=").parse().unwrap();
+            paginator(page, 10)
+        };
+
+        // The budget covers all the pages, so no warning is raised
+        let outcome = collect_with_budget("?page=1".to_string(), 3, fetch_page)
+            .await
+            .unwrap();
+        assert!(outcome.is_complete());
+        assert_eq!(outcome.value, (0..25).collect::<Vec<_>>());
+
+        // Otherwise the partial results are returned along with the cursor to resume from
+        let outcome = collect_with_budget("?page=1".to_string(), 2, fetch_page)
+            .await
+            .unwrap();
+        assert_eq!(outcome.value, (0..20).collect::<Vec<_>>());
+        assert!(matches!(
+            outcome.warnings.as_slice(),
+            [Warning::BudgetExceeded { request_budget: 2, next }] if next == "?page=3"
+        ));
+
+        let outcome = collect_with_budget("?page=1".to_string(), 0, fetch_page)
+            .await
+            .unwrap();
+        assert!(outcome.value.is_empty());
+        assert_eq!(outcome.warnings.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_pagination_surfaces_page_errors() {
+        // The first page is served fine, whereas the rest of the pages fail with a 500
+        let first_page = http_response(
+            "200 OK",
+            &[("Content-Type", "application/json".to_string())],
+            &json!({
+                "count": 30,
+                "next": "?page=2",
+                "previous": null,
+                "results": (0..10).collect::<Vec<_>>(),
+            })
+            .to_string(),
+        );
+        let server_error = http_response("500 Internal Server Error", &[], "");
+        let (url, _) = mock_server(vec![first_page, server_error]).await;
+        let client = DockerHubClient::builder()
+            .base_url(url.clone())
+            .max_retries(0)
+            .build()
+            .unwrap();
+
+        for fail_fast in [false, true] {
+            let options = PaginationOptions {
+                fail_fast,
+                ..Default::default()
+            };
+            let error = fetch_with_pagination_with_options::<usize>(&client, &url, &options)
+                .await
+                .unwrap_err();
+            assert_eq!(
+                error.status(),
+                Some(reqwest::StatusCode::INTERNAL_SERVER_ERROR)
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_paginate_fail_fast_aborts_remaining_pages() {
+        use std::pin::Pin;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        /// Counts the page requests dropped before completing i.e. aborted
+        struct DropGuard(Arc<AtomicUsize>);
+
+        impl Drop for DropGuard {
+            fn drop(&mut self) {
+                self.0.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        let (url, _) = mock_server(vec![http_response("500 Internal Server Error", &[], "")]).await;
+        let client = DockerHubClient::builder()
+            .base_url(url.clone())
+            .max_retries(0)
+            .build()
+            .unwrap();
+
+        // The second page fails with a 500 served by the mock server, whereas the rest of the
+        // pages never complete, so that those can only be dropped by aborting them
+        let aborted = Arc::new(AtomicUsize::new(0));
+        let paginator = mock_paginator(50);
+        let options = PaginationOptions {
+            fail_fast: true,
+            ..Default::default()
+        };
+        let error = paginate(&options, MAX_CONCURRENT_REQUESTS, |page, page_size| {
+            let future: Pin<Box<dyn Future<Output = _> + Send>> = match page {
+                1 => Box::pin(paginator(page, page_size)),
+                2 => {
+                    let client = client.clone();
+                    let url = url.clone();
+                    Box::pin(async move {
+                        fetch::<ApiResult<usize>>(&client, &url, Some(page), Some(page_size)).await
+                    })
+                }
+                _ => {
+                    let guard = DropGuard(aborted.clone());
+                    Box::pin(async move {
+                        let _guard = guard;
+                        futures::future::pending().await
+                    })
+                }
+            };
+            future
+        })
+        .await
+        .unwrap_err();
+        assert_eq!(
+            error.status(),
+            Some(reqwest::StatusCode::INTERNAL_SERVER_ERROR)
+        );
+
+        // The pages 3, 4, and 5 are aborted, which drops those asynchronously
+        tokio::time::timeout(std::time::Duration::from_secs(5), async {
+            while aborted.load(Ordering::SeqCst) < 3 {
+                tokio::task::yield_now().await;
+            }
+        })
+        .await
+        .expect("the remaining page requests were not aborted");
+    }
+
+    #[tokio::test]
+    async fn test_paginate_empty_first_page() {
+        let options = PaginationOptions::default();
+        let results = paginate(&options, MAX_CONCURRENT_REQUESTS, |page, _| {
+            futures::future::ready(Ok(ApiResult::<usize> {
+                count: 0,
+                next: Some(format!("?page={}", page + 1)),
+                previous: None,
+                results: Vec::new(),
+            }))
+        })
+        .await
+        .unwrap();
+        assert!(results.is_empty());
+    }
+
+    /// Serves the provided raw HTTP `responses` in order on a local port, one per connection
+    /// and repeating the last one, returning its URL and the number of requests received
+    pub(crate) async fn mock_server(
+        responses: Vec<String>,
+    ) -> (Url, Arc<std::sync::atomic::AtomicUsize>) {
+        mock_server_with(|_| responses).await
+    }
+
+    /// Same as `mock_server` but building the `responses` from the URL of the server, so that
+    /// those can point back to it e.g. via the `next` URL of a page
+    pub(crate) async fn mock_server_with(
+        responses: impl FnOnce(&Url) -> Vec<String>,
+    ) -> (Url, Arc<std::sync::atomic::AtomicUsize>) {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = Url::parse(&format!("http://{}", listener.local_addr().unwrap())).unwrap();
+        let responses = responses(&url);
+        let requests = Arc::new(AtomicUsize::new(0));
+
+        let counter = requests.clone();
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut request = Vec::new();
+                let mut buffer = [0; 1024];
+                while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+                    let read = stream.read(&mut buffer).await.unwrap();
+                    if read == 0 {
+                        break;
+                    }
+                    request.extend_from_slice(&buffer[..read]);
+                }
+                let index = counter.fetch_add(1, Ordering::SeqCst);
+                let response = &responses[index.min(responses.len() - 1)];
+                stream.write_all(response.as_bytes()).await.unwrap();
+                stream.shutdown().await.unwrap();
+            }
+        });
+
+        (url, requests)
+    }
+
+    pub(crate) fn http_response(status: &str, headers: &[(&str, String)], body: &str) -> String {
+        let mut response = format!("HTTP/1.1 {status}\r\nConnection: close\r\n");
+        for (name, value) in headers {
+            response.push_str(&format!("{name}: {value}\r\n"));
+        }
+        format!("{response}Content-Length: {}\r\n\r\n{body}", body.len())
+    }
+
+    /// Returns a 200 response with the provided JSON `body`
+    pub(crate) fn json_response(body: &Value) -> String {
+        http_response(
+            "200 OK",
+            &[("Content-Type", "application/json".to_string())],
+            &body.to_string(),
+        )
+    }
+
+    /// Returns the JSON payload of a repository named `name` under the `ollama` namespace, so
+    /// that the tests only override the fields those care about
+    pub(crate) fn repository_json(name: &str) -> Value {
+        json!({
+          "name": name,
+          "namespace": "ollama",
+          "repository_type": "image",
+          "status": 1,
+          "status_description": "active",
+          "description": "The easiest way to get up and running with large language models.",
+          "is_private": false,
+          "star_count": 1183,
+          "pull_count": 13256501,
+          "last_updated": "2025-03-04T04:01:22.754331Z",
+          "last_modified": "2024-10-16T13:48:34.145251Z",
+          "date_registered": "2023-06-29T23:27:34.326426Z",
+          "affiliation": "",
+          "media_types": [
+            "application/vnd.oci.image.index.v1+json"
+          ],
+          "content_types": [
+            "image"
+          ],
+          "categories": [],
+          "storage_size": 662988133055_u64,
+        })
+    }
+
+    /// Returns the JSON payload of a tag named `name` with a single linux/amd64 image, so that
+    /// the tests only override the fields those care about
+    pub(crate) fn tag_json(name: &str) -> Value {
+        json!({
+          "creator": 14304909,
+          "id": 529481097,
+          "images": [
+            {
+              "architecture": "amd64",
+              "features": "",
+              "variant": null,
+              "digest": "sha256:96b6a4e66250499a9d87a4adf259ced7cd213e2320fb475914217f4d69abe98d",
+              "os": "linux",
+              "os_features": "",
+              "os_version": null,
+              "size": 755930694,
+              "status": "active",
+              "last_pulled": "2025-03-05T07:52:00.613197154Z",
+              "last_pushed": "2024-01-16T20:54:52Z"
+            }
+          ],
+          "last_updated": "2024-01-16T20:54:55.914808Z",
+          "last_updater": 14304909,
+          "last_updater_username": "mxyng",
+          "name": name,
+          "repository": 22180121,
+          "full_size": 755930694,
+          "v2": true,
+          "tag_status": "active",
+          "tag_last_pulled": "2025-03-05T07:52:00.613197154Z",
+          "tag_last_pushed": "2024-01-16T20:54:55.914808Z",
+          "media_type": "application/vnd.oci.image.index.v1+json",
+          "content_type": "image",
+          "digest": "sha256:7c49490a9e4a7ca4326e09c4b47bc525aa0a9dfc8ea0b3a30d62af23a60db712"
+        })
+    }
+
+    #[tokio::test]
+    async fn test_rate_limited_request_is_retried() {
+        use std::sync::atomic::Ordering;
+
+        use crate::clock::MockClock;
+
+        let start = DateTime::from_timestamp(1741600000, 0).unwrap();
+        let retry_after = ("X-Retry-After", (start.timestamp() + 30).to_string());
+        let rate_limited = http_response("429 Too Many Requests", &[retry_after], "");
+        let ok = http_response(
+            "200 OK",
+            &[("Content-Type", "application/json".to_string())],
+            r#"{"count": 0, "next": null, "previous": null, "results": []}"#,
+        );
+
+        let (url, requests) = mock_server(vec![rate_limited.clone(), ok]).await;
+        let clock = MockClock::new(start);
+        let client = DockerHubClient::builder()
+            .base_url(url.clone())
+            .clock(clock.clone())
+            .build()
+            .unwrap();
+
+        let result =
+            fetch::<ApiResult<Category>>(&client, &url.join("v2/categories").unwrap(), None, None)
+                .await
+                .unwrap();
+        assert_eq!(result.count(), 0);
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+        assert_eq!(clock.sleeps(), vec![std::time::Duration::from_secs(30)]);
+
+        // The retries are capped, returning the rate limit error once exhausted
+        let (url, requests) = mock_server(vec![rate_limited]).await;
+        let client = DockerHubClient::builder()
+            .base_url(url.clone())
+            .clock(MockClock::new(start))
+            .max_retries(1)
+            .build()
+            .unwrap();
+
+        let error = fetch::<Value>(&client, &url, None, None).await.unwrap_err();
+        assert!(matches!(error.inner(), HubError::RateLimited { .. }));
+        assert!(error.to_string().starts_with("giving up after 1 retries"));
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+
+        // Whereas a rate limit lifted later than the maximum backoff isn't waited for
+        let retry_after = start + chrono::TimeDelta::hours(6);
+        let (url, requests) = mock_server(vec![http_response(
+            "429 Too Many Requests",
+            &[("X-Retry-After", retry_after.timestamp().to_string())],
+            "",
+        )])
+        .await;
+        let clock = MockClock::new(start);
+        let client = DockerHubClient::builder()
+            .base_url(url.clone())
+            .clock(clock.clone())
+            .build()
+            .unwrap();
+
+        let error = fetch::<Value>(&client, &url, None, None).await.unwrap_err();
+        assert!(matches!(
+            error.inner(),
+            HubError::RateLimited { retry_after: Some(at), .. } if *at == retry_after
+        ));
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+        assert!(clock.sleeps().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_middleware_is_applied_on_every_attempt() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        use crate::clock::MockClock;
+
+        let unavailable = http_response("503 Service Unavailable", &[], "");
+        let ok = http_response(
+            "200 OK",
+            &[("Content-Type", "application/json".to_string())],
+            "[]",
+        );
+        let (url, requests) = mock_server(vec![unavailable, ok]).await;
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let client = DockerHubClient::builder()
+            .base_url(url.clone())
+            .clock(MockClock::new(Utc::now()))
+            .middleware(move |request| {
+                counter.fetch_add(1, Ordering::SeqCst);
+                request.header("X-Signature", "signed")
+            })
+            .build()
+            .unwrap();
+
+        let categories = fetch::<Vec<Category>>(&client, &url, None, None)
+            .await
+            .unwrap();
+        assert!(categories.is_empty());
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_client_without_token() {
+        use std::sync::Mutex;
+
+        let page = json!({ "count": 0, "next": null, "previous": null, "results": [] });
+        let (url, _) = mock_server(vec![json_response(&page)]).await;
+
+        // The middleware records whether each request carries the token
+        let authorized = Arc::new(Mutex::new(Vec::new()));
+        let recorded = authorized.clone();
+        let client = DockerHubClient::builder()
+            .token("dckr_pat_1234")
+            .base_url(url.clone())
+            .max_concurrent_requests(4)
+            .middleware(move |request| {
+                let headers = request.try_clone().unwrap().build().unwrap();
+                recorded
+                    .lock()
+                    .unwrap()
+                    .push(headers.headers().contains_key(header::AUTHORIZATION));
+                request
+            })
+            .build()
+            .unwrap();
+        let anonymous = client.without_token();
+        assert_eq!(anonymous.url, client.url);
+        assert_eq!(anonymous.max_concurrent_requests, 4);
+        assert!(Arc::ptr_eq(&anonymous.clock, &client.clock));
+
+        fetch::<Value>(&client, &url, None, None).await.unwrap();
+        fetch::<Value>(&anonymous, &url, None, None).await.unwrap();
+        assert_eq!(*authorized.lock().unwrap(), vec![true, false]);
+    }
+
+    #[tokio::test]
+    async fn test_client_without_token_reuses_the_provided_client() {
+        let page = json!({ "count": 0, "next": null, "previous": null, "results": [] });
+        let (url, _) = mock_server(vec![json_response(&page)]).await;
+
+        // The hostname is only resolvable via the caller-provided Client, so the requests only
+        // succeed if that Client is the one sending those
+        let addr = SocketAddr::new(
+            url.host_str().unwrap().parse().unwrap(),
+            url.port().unwrap(),
+        );
+        let custom = Client::builder()
+            .resolve("hub.invalid", addr)
+            .build()
+            .unwrap();
+        let base_url = Url::parse(&format!("http://hub.invalid:{}", addr.port())).unwrap();
+        let client = DockerHubClient::from_client(custom, base_url.clone());
+
+        let anonymous = client.without_token();
+        fetch::<Value>(&anonymous, &base_url, None, None)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_fetch_page_from_cursor_rejects_other_origins() {
+        use std::sync::atomic::Ordering;
+
+        let page = r#"{"count": 0, "next": null, "previous": null, "results": []}"#;
+        let (url, requests) = mock_server(vec![http_response(
+            "200 OK",
+            &[("Content-Type", "application/json".to_string())],
+            page,
+        )])
+        .await;
+        let client = DockerHubClient::builder()
+            .base_url(url.clone())
+            .build()
+            .unwrap();
+
+        let cursor = url.join("v2/categories?page=2").unwrap();
+        assert!(fetch_page_from_cursor::<Category>(&client, cursor.as_str())
+            .await
+            .is_ok());
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+
+        for cursor in [
+            "https://example.com/v2/categories?page=2",
+            &format!("https://{}/v2/categories?page=2", url.authority()),
+        ] {
+            assert!(fetch_page_from_cursor::<Category>(&client, cursor)
+                .await
+                .is_err());
+        }
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_request_id_in_error() {
+        let not_found = http_response("404 Not Found", &[("X-Trace-Id", "abcd".to_string())], "");
+        let (url, _) = mock_server(vec![not_found]).await;
+        let client = DockerHubClient::builder()
+            .base_url(url.clone())
+            .request_id_header(true)
+            .build()
+            .unwrap();
+
+        let error = fetch::<Value>(&client, &url, None, None).await.unwrap_err();
+        let HubError::Response {
+            request_id: Some(request_id),
+            diagnostics,
+            ..
+        } = &error
+        else {
+            panic!("expected the request details within the error, got {error:?}");
+        };
+        assert!(error
+            .to_string()
+            .contains(&format!("request id: {request_id}")));
+        assert_eq!(diagnostics.as_deref(), Some("x-trace-id: abcd"));
+        assert!(matches!(error.inner(), HubError::NotFound(_)));
+    }
+
+    #[tokio::test]
+    async fn test_server_error_keeps_unexpected_content_type() {
+        let bad_gateway = http_response(
+            "502 Bad Gateway",
+            &[("Content-Type", "text/html".to_string())],
+            "<html>bad gateway</html>",
+        );
+        let (url, _) = mock_server(vec![bad_gateway]).await;
+        let client = DockerHubClient::builder()
+            .base_url(url.clone())
+            .max_retries(0)
+            .build()
+            .unwrap();
+
+        let error = fetch::<Value>(&client, &url, None, None).await.unwrap_err();
+        let HubError::Http {
+            source: Some(source),
+            ..
+        } = &error
+        else {
+            panic!("expected the unexpected content type within the error, got {error:?}");
+        };
+        assert!(matches!(
+            source.as_ref(),
+            HubError::UnexpectedContentType { snippet, .. } if snippet == "<html>bad gateway</html>"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_status_errors() {
+        for (status, expected) in [
+            (
+                "401 Unauthorized",
+                "provided client is not authorized to request {url}",
+            ),
+            (
+                "403 Forbidden",
+                "access to {url} is forbidden for the provided token",
+            ),
+            ("404 Not Found", "{url} not found"),
+            (
+                "429 Too Many Requests",
+                "too many requests sent to the docker hub requesting {url}",
+            ),
+            (
+                "500 Internal Server Error",
+                "request to {url} failed with status code 500 Internal Server Error",
+            ),
+        ] {
+            let (url, _) = mock_server(vec![http_response(status, &[], "")]).await;
+            let client = DockerHubClient::builder()
+                .base_url(url.clone())
+                .max_retries(0)
+                .build()
+                .unwrap();
+
+            let url = url.join("v2/categories").unwrap();
+            let error = fetch::<Value>(&client, &url, None, None).await.unwrap_err();
+            assert_eq!(error.to_string(), expected.replace("{url}", url.as_str()));
+            assert_eq!(error.url(), Some(&url));
+        }
+    }
+
+    #[test]
+    fn test_default_namespace() {
+        let client = DockerHubClient::builder().build().unwrap();
+        assert!(client.require_default_namespace().is_err());
+
+        let client = DockerHubClient::builder()
+            .default_namespace("ollama")
+            .build()
+            .unwrap();
+        assert_eq!(client.require_default_namespace().unwrap(), "ollama");
+    }
+
+    #[test]
+    fn test_page_query_clamps_page_size() {
+        assert_eq!(page_query(1, 10), [("page", 1), ("page_size", 10)]);
+        assert_eq!(page_query(2, 100), [("page", 2), ("page_size", 100)]);
+        assert_eq!(page_query(3, 500), [("page", 3), ("page_size", 100)]);
+    }
+
+    #[test]
+    fn test_diagnostic_headers() {
+        let mut headers = header::HeaderMap::new();
+        headers.insert("x-request-id", "abc123".parse().unwrap());
+        headers.insert("x-served-by", "hub-api-1".parse().unwrap());
+        headers.insert(header::CONTENT_TYPE, "application/json".parse().unwrap());
+
+        assert_eq!(
+            diagnostic_headers(&headers),
+            "x-request-id: abc123, x-served-by: hub-api-1"
+        );
+        assert_eq!(diagnostic_headers(&header::HeaderMap::new()), "");
+    }
+
+    #[test]
+    fn test_from_value_reports_field_path() {
+        let mut tag = tag_json("gguf");
+        tag["images"][0]["last_pushed"] = json!(1705438492);
+        let value = json!({ "count": 1, "next": null, "previous": null, "results": [tag] });
+
+        let error = from_value::<ApiResult<Tag>>(value).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("results[0].images[0].last_pushed"));
+    }
+}
@@ -0,0 +1,558 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeSet, BinaryHeap};
+
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use futures::stream::{self, StreamExt};
+use reqwest::{Method, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::error::HubError;
+use crate::{fetch, fetch_with_pagination, send, send_request, DockerHubClient, Outcome, Warning};
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RestrictedImages {
+    /// Whether the image access management is enabled for the organization
+    pub enabled: bool,
+
+    /// Whether the members are allowed to pull the Docker Official Images
+    pub allow_official_images: bool,
+
+    /// Whether the members are allowed to pull the Docker Verified Publisher images
+    pub allow_verified_publishers: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct OrgSettings {
+    /// The default visibility for the new repositories created under the organization, can be
+    /// any of "public" or "private"
+    #[serde(default)]
+    default_repo_visibility: Option<String>,
+
+    /// The image access management settings for the organization members
+    #[serde(default)]
+    restricted_images: Option<RestrictedImages>,
+}
+
+impl OrgSettings {
+    /// Returns the default visibility for the new repositories, if reported by the Docker Hub
+    pub fn default_repo_visibility(&self) -> Option<&str> {
+        self.default_repo_visibility.as_deref()
+    }
+
+    /// Returns the image access management settings, if reported by the Docker Hub
+    pub fn restricted_images(&self) -> Option<&RestrictedImages> {
+        self.restricted_images.as_ref()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Plan {
+    /// The name of the plan e.g. "team"
+    #[serde(default)]
+    name: Option<String>,
+
+    /// The storage quota in bytes, if limited
+    #[serde(default)]
+    storage_limit: Option<u64>,
+
+    /// The maximum number of private repositories, if limited
+    #[serde(default)]
+    private_repo_limit: Option<u64>,
+}
+
+impl Plan {
+    /// Returns the name of the plan, if reported by the Docker Hub
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Returns the storage quota in bytes, or `None` if unlimited
+    pub fn storage_limit(&self) -> Option<u64> {
+        self.storage_limit
+    }
+
+    /// Returns the maximum number of private repositories, or `None` if unlimited
+    pub fn private_repo_limit(&self) -> Option<u64> {
+        self.private_repo_limit
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct StorageReport {
+    /// The sum of the storage size in bytes across all the repositories
+    pub total_size: u64,
+
+    /// The storage quota in bytes as per the plan, if available and limited
+    pub quota: Option<u64>,
+
+    /// The percentage of the quota used, if the quota is available
+    pub percent_used: Option<f64>,
+
+    /// The name and storage size in bytes of each repository, sorted by size descending
+    pub repositories: Vec<(String, u64)>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Invitation {
+    /// The ID of the invitation, required to revoke it
+    id: String,
+
+    /// The email (or Docker ID) of the invitee
+    email: String,
+
+    /// The team within the organization that the invitee will join, if any
+    #[serde(default)]
+    team: Option<String>,
+
+    /// The status of the invitation e.g. "pending"
+    status: String,
+
+    /// When the invitation was sent
+    created_at: DateTime<Utc>,
+}
+
+impl Invitation {
+    /// Returns the ID of the invitation
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Returns the email (or Docker ID) of the invitee
+    pub fn email(&self) -> &str {
+        &self.email
+    }
+
+    /// Returns the team that the invitee will join, if any
+    pub fn team(&self) -> Option<&str> {
+        self.team.as_deref()
+    }
+
+    /// Returns the status of the invitation
+    pub fn status(&self) -> &str {
+        &self.status
+    }
+
+    /// Returns when the invitation was sent
+    pub fn created_at(&self) -> DateTime<Utc> {
+        self.created_at
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ImageRefSize {
+    /// The size of the tag in bytes, first so that the ordering is by size
+    pub full_size: u64,
+
+    /// The name of the repository
+    pub repository: String,
+
+    /// The name of the tag
+    pub tag: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Eq)]
+pub struct OrgDiff {
+    /// The names of the repositories that only exist in the first org, sorted alphabetically
+    pub only_in_a: Vec<String>,
+
+    /// The names of the repositories that only exist in the second org, sorted alphabetically
+    pub only_in_b: Vec<String>,
+
+    /// The names of the repositories that exist in both orgs, sorted alphabetically
+    pub in_both: Vec<String>,
+}
+
+impl DockerHubClient {
+    /// Get the plan for a given organization on the Docker Hub
+    ///
+    /// This method retrieves the plan of the organization provided via the `org` argument,
+    /// including its limits e.g. the storage quota or the maximum number of private
+    /// repositories. Note that the provided token needs to have admin access to the org.
+    pub async fn org_plan(&self, org: &str) -> anyhow::Result<Plan> {
+        let url = self
+            .url
+            .join(&format!("v2/orgs/{}/plan", org))
+            .context("failed formatting the url with the provided org")?;
+
+        fetch::<Plan>(self, &url, None, None)
+            .await
+            .context("fetching the provided url failed")
+    }
+
+    /// Compute the storage usage for a given org or username on the Docker Hub
+    ///
+    /// This method sums the storage size of all the repositories under the given `org`, and
+    /// compares it against the storage quota of the plan when available i.e. when the plan
+    /// endpoint is accessible for the provided token i.e. the quota is only left empty when the
+    /// plan endpoint responds with either a 401, a 403, or a 404; whereas any other failure
+    /// reading the plan is returned. The
+    /// repositories whose storage size cannot be fetched are left out of the report, and
+    /// reported back as warnings instead.
+    pub async fn storage_report(&self, org: &str) -> anyhow::Result<Outcome<StorageReport>> {
+        let Outcome {
+            value: repositories,
+            warnings,
+        } = self
+            .list_repositories_with_storage(org)
+            .await
+            .context("listing the repositories failed")?;
+
+        let mut repositories = repositories
+            .into_iter()
+            .map(|repository| {
+                let size = repository.storage_size();
+                (repository.name, size)
+            })
+            .collect::<Vec<_>>();
+        repositories.sort_by_key(|(_, size)| Reverse(*size));
+
+        let total_size = repositories.iter().map(|(_, size)| size).sum::<u64>();
+        let quota = match self.org_plan(org).await {
+            Ok(plan) => plan.storage_limit(),
+            Err(e) => match e.downcast_ref::<HubError>().and_then(HubError::status) {
+                Some(StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN | StatusCode::NOT_FOUND) => {
+                    None
+                }
+                _ => return Err(e.context("reading the plan failed")),
+            },
+        };
+        let percent_used = quota
+            .filter(|quota| *quota > 0)
+            .map(|quota| total_size as f64 / quota as f64 * 100.0);
+
+        Ok(Outcome {
+            value: StorageReport {
+                total_size,
+                quota,
+                percent_used,
+                repositories,
+            },
+            warnings,
+        })
+    }
+
+    /// Compute how many private repositories can still be created under a given organization
+    ///
+    /// This method compares the number of private repositories under the `org` against the
+    /// private repositories limit of its plan, returning the remaining slots (zero if already
+    /// over the limit), or `None` if the plan doesn't limit the private repositories. Note that
+    /// the provided token needs to have admin access to the org to read its plan.
+    pub async fn private_repo_headroom(&self, org: &str) -> anyhow::Result<Option<u64>> {
+        let plan = self.org_plan(org).await.with_context(|| {
+            format!("couldn't read the plan of {org}, the token may lack admin access to it")
+        })?;
+        let Some(limit) = plan.private_repo_limit() else {
+            return Ok(None);
+        };
+
+        let repositories = self
+            .list_repositories(org)
+            .await
+            .context("listing the repositories failed")?;
+        let private = repositories
+            .iter()
+            .filter(|repository| repository.is_private())
+            .count() as u64;
+
+        Ok(Some(limit.saturating_sub(private)))
+    }
+
+    /// Compare the repositories under two orgs or usernames on the Docker Hub
+    ///
+    /// This method lists the repositories of both `org_a` and `org_b` concurrently, and returns
+    /// the names of the repositories that only exist in either of those, and the ones that exist
+    /// in both; which is useful to e.g. verify that a namespace migration copied everything.
+    pub async fn diff_orgs(&self, org_a: &str, org_b: &str) -> anyhow::Result<OrgDiff> {
+        let (repositories_a, repositories_b) =
+            futures::try_join!(self.list_repositories(org_a), self.list_repositories(org_b))
+                .context("listing the repositories failed")?;
+
+        let names_a = repositories_a
+            .into_iter()
+            .map(|repository| repository.name)
+            .collect::<BTreeSet<_>>();
+        let names_b = repositories_b
+            .into_iter()
+            .map(|repository| repository.name)
+            .collect::<BTreeSet<_>>();
+
+        Ok(OrgDiff {
+            only_in_a: names_a.difference(&names_b).cloned().collect(),
+            only_in_b: names_b.difference(&names_a).cloned().collect(),
+            in_both: names_a.intersection(&names_b).cloned().collect(),
+        })
+    }
+
+    /// List the largest tags across all the repositories under a given org or username
+    ///
+    /// This method lists the tags of every repository under the `org`, concurrently but with a
+    /// bounded parallelism, and returns the `top_n` largest ones sorted by size descending; which
+    /// is useful to decide what to clean up to reduce the storage usage. As listing all the tags
+    /// of an org may take a while, the optional `on_progress` callback is called after each
+    /// repository with the number of repositories processed so far and the total. The
+    /// repositories whose tags cannot be listed are skipped, and reported back as warnings.
+    pub async fn largest_images(
+        &self,
+        org: &str,
+        top_n: usize,
+        mut on_progress: Option<&mut (dyn FnMut(usize, usize) + Send)>,
+    ) -> anyhow::Result<Outcome<Vec<ImageRefSize>>> {
+        let repositories = self
+            .list_repositories(org)
+            .await
+            .context("listing the repositories failed")?;
+        let total = repositories.len();
+
+        let mut tags = stream::iter(repositories)
+            .map(|repository| async move {
+                let tags = self
+                    .list_tags(org, &repository.name)
+                    .await
+                    .with_context(|| format!("listing the tags of {} failed", repository.name));
+                (repository.name, tags)
+            })
+            .buffer_unordered(self.max_concurrent_requests);
+
+        // Only the `top_n` largest tags are kept at any time, so that the memory usage stays
+        // bounded regardless of the number of tags
+        let mut largest = BinaryHeap::with_capacity(top_n + 1);
+        let mut warnings = Vec::new();
+        let mut done = 0;
+        while let Some((repository, result)) = tags.next().await {
+            done += 1;
+            if let Some(on_progress) = on_progress.as_mut() {
+                on_progress(done, total);
+            }
+
+            let tags = match result {
+                Ok(tags) => tags,
+                Err(error) => {
+                    warnings.push(Warning::ItemFailed {
+                        item: repository,
+                        error,
+                    });
+                    continue;
+                }
+            };
+            for tag in tags {
+                largest.push(Reverse(ImageRefSize {
+                    full_size: tag.full_size(),
+                    repository: repository.clone(),
+                    tag: tag.name().to_string(),
+                }));
+                if largest.len() > top_n {
+                    largest.pop();
+                }
+            }
+        }
+
+        Ok(Outcome {
+            value: largest
+                .into_sorted_vec()
+                .into_iter()
+                .map(|Reverse(image)| image)
+                .collect(),
+            warnings,
+        })
+    }
+
+    /// List the pending invitations for a given organization on the Docker Hub
+    ///
+    /// Note that the provided token needs to have admin access to the org.
+    pub async fn list_invitations(&self, org: &str) -> Result<Vec<Invitation>, HubError> {
+        let url = self
+            .url
+            .join(&format!("v2/orgs/{}/invites", org))
+            .context("failed formatting the url with the provided org")?;
+
+        fetch_with_pagination::<Invitation>(self, &url).await
+    }
+
+    /// Invite a member to join a team within a given organization on the Docker Hub
+    ///
+    /// This method sends an invitation to the provided `email` (or Docker ID) to join the
+    /// `team` within the `org`. Note that the provided token needs to have admin access to
+    /// the org, and that the request is not retried, to avoid sending the invitation twice.
+    pub async fn invite_member(&self, org: &str, team: &str, email: &str) -> anyhow::Result<()> {
+        let url = self
+            .url
+            .join("v2/invites/bulk")
+            .context("failed formatting the invites url")?;
+
+        let body = json!({
+            "org": org,
+            "team": team,
+            "role": "member",
+            "invitees": [email],
+        });
+        send::<_, Value>(self, Method::POST, &url, &body)
+            .await
+            .context("inviting the member failed")?;
+        Ok(())
+    }
+
+    /// Revoke a pending invitation via its `id`, as returned by `list_invitations`
+    ///
+    /// Note that the request is not retried on transient failures, same as any other `DELETE`.
+    pub async fn revoke_invitation(&self, id: &str) -> anyhow::Result<()> {
+        let url = self
+            .url
+            .join(&format!("v2/invites/{}", id))
+            .context("failed formatting the url with the provided invitation id")?;
+
+        send_request(self, &url, self.client.delete(url.clone()))
+            .await
+            .context("revoking the invitation failed")?;
+        Ok(())
+    }
+
+    /// Get the settings for a given organization on the Docker Hub
+    ///
+    /// This method retrieves the default settings of the organization provided via the `org`
+    /// argument, which is useful to verify org-wide policies e.g. that the new repositories are
+    /// private by default. Note that the provided token needs to have admin access to the org.
+    pub async fn org_settings(&self, org: &str) -> anyhow::Result<OrgSettings> {
+        let url = self
+            .url
+            .join(&format!("v2/orgs/{}/settings", org))
+            .context("failed formatting the url with the provided org")?;
+
+        fetch::<OrgSettings>(self, &url, None, None)
+            .await
+            .context("fetching the provided url failed")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_storage_report_without_plan() {
+        use crate::tests::{http_response, mock_server};
+
+        let empty = http_response(
+            "200 OK",
+            &[("Content-Type", "application/json".to_string())],
+            r#"{"count": 0, "next": null, "previous": null, "results": []}"#,
+        );
+
+        // The plan being inaccessible to the token only leaves the quota empty
+        let (url, _) =
+            mock_server(vec![empty.clone(), http_response("403 Forbidden", &[], "")]).await;
+        let client = DockerHubClient::builder().base_url(url).build().unwrap();
+        let report = client.storage_report("ollama").await.unwrap();
+        assert_eq!(report.value.quota, None);
+        assert_eq!(report.value.percent_used, None);
+
+        // Whereas any other failure e.g. a server error is returned
+        let (url, _) = mock_server(vec![
+            empty,
+            http_response("500 Internal Server Error", &[], ""),
+        ])
+        .await;
+        let client = DockerHubClient::builder()
+            .base_url(url)
+            .max_retries(0)
+            .build()
+            .unwrap();
+        let error = client.storage_report("ollama").await.unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<HubError>().and_then(HubError::status),
+            Some(StatusCode::INTERNAL_SERVER_ERROR)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_largest_images_skips_unreadable_repositories() {
+        use crate::tests::{http_response, json_response, mock_server, repository_json, tag_json};
+
+        let page = |results: Vec<Value>| json!({ "count": results.len(), "next": null, "previous": null, "results": results });
+        let tag = |name: &str, full_size: u64| {
+            let mut value = tag_json(name);
+            value["full_size"] = json!(full_size);
+            value
+        };
+
+        // The repositories are processed one after the other, so that the responses are served
+        // in order: the listing, and then the tags of each repository
+        let (url, _) = mock_server(vec![
+            json_response(&page(vec![
+                repository_json("ollama"),
+                repository_json("quantize"),
+            ])),
+            json_response(&page(vec![tag("0.6.0", 2048), tag("0.5.13", 1024)])),
+            http_response("404 Not Found", &[], ""),
+        ])
+        .await;
+        let client = DockerHubClient::builder()
+            .base_url(url)
+            .max_concurrent_requests(1)
+            .build()
+            .unwrap();
+
+        let mut progress = Vec::new();
+        let mut on_progress = |done, total| progress.push((done, total));
+        let outcome = client
+            .largest_images("ollama", 1, Some(&mut on_progress))
+            .await
+            .unwrap();
+        assert_eq!(
+            outcome.value,
+            vec![ImageRefSize {
+                full_size: 2048,
+                repository: "ollama".to_string(),
+                tag: "0.6.0".to_string(),
+            }]
+        );
+        assert!(matches!(
+            outcome.warnings.as_slice(),
+            [Warning::ItemFailed { item, .. }] if item == "quantize"
+        ));
+        assert_eq!(progress, vec![(1, 2), (2, 2)]);
+    }
+
+    #[test]
+    fn test_org_settings_serde() {
+        let value = json!({
+          "default_repo_visibility": "private",
+          "restricted_images": {
+            "enabled": true,
+            "allow_official_images": true,
+            "allow_verified_publishers": false
+          }
+        });
+
+        let settings = serde_json::from_value::<OrgSettings>(value)
+            .context("failed to deserialize the org settings payload")
+            .unwrap();
+
+        assert_eq!(settings.default_repo_visibility(), Some("private"));
+        assert!(settings.restricted_images().unwrap().enabled);
+
+        println!("{settings:#?}");
+    }
+
+    #[test]
+    fn test_invitation_serde() {
+        let value = json!({
+          "id": "e36eb3e5-6b4e-4d3c-8a2a-2d0d1f1c9b1e",
+          "email": "jane@example.com",
+          "team": "owners",
+          "status": "pending",
+          "created_at": "2025-03-04T04:01:22.754331Z"
+        });
+
+        let invitation = serde_json::from_value::<Invitation>(value)
+            .context("failed to deserialize the invitation payload")
+            .unwrap();
+
+        assert_eq!(invitation.email(), "jane@example.com");
+        assert_eq!(invitation.team(), Some("owners"));
+
+        println!("{invitation:#?}");
+    }
+}
@@ -0,0 +1,22 @@
+//! The prelude re-exports the most commonly used types, so that those can be imported at once
+//!
+//! ```rust
+//! use hub_tool::prelude::*;
+//! ```
+
+pub use crate::error::HubError;
+pub use crate::orgs::{OrgSettings, Plan};
+pub use crate::repositories::{
+    Category, ContentSummary, ContentType, Freshness, Repository, RepositoryInspection,
+    VisibilitySummary,
+};
+pub use crate::snapshots::{
+    diff_pull_counts, diff_snapshots, OrgSnapshot, RepoSnapshot, SnapshotDiff,
+};
+pub use crate::tags::{Image, MediaType, Platform, Tag};
+pub use crate::users::User;
+pub use crate::utils::{format_bytes, ByteUnits};
+pub use crate::{
+    DockerHubClient, DockerHubClientBuilder, Middleware, Namespace, Outcome, RedirectPolicy,
+    Warning,
+};
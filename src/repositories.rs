@@ -1,15 +1,42 @@
+use std::collections::BTreeSet;
+use std::hash::{Hash, Hasher};
+
 use anyhow::Context;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, TimeDelta, Utc};
+use futures::stream::{self, StreamExt};
+use reqwest::{Method, StatusCode};
 use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
 
-use crate::{fetch_with_pagination, DockerHubClient};
+use crate::error::HubError;
+use crate::tags::Tag;
+use crate::utils::{deserialize_number_or_string, empty_string_as_none, resolve_repository};
+use crate::{
+    fetch, fetch_with_pagination, send_with_options, ApiResult, DockerHubClient, Namespace,
+    Outcome, SendOptions, Warning,
+};
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Category {
+    /// The display name of the category e.g. "Machine Learning & AI"
     name: String,
+
+    /// The slug of the category e.g. "machine-learning-and-ai"
     slug: String,
 }
 
+impl Category {
+    /// Returns the display name of the category
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the slug of the category
+    pub fn slug(&self) -> &str {
+        &self.slug
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Repository {
     /// The name of the repository on the Docker Hub
@@ -19,41 +46,401 @@ pub struct Repository {
     namespace: String,
 
     /// The type of repository, can be any of "image", etc.
+    #[serde(alias = "repositoryType")]
     repository_type: String,
 
     status: usize,
 
+    #[serde(alias = "statusDescription")]
     status_description: String,
 
-    // TODO: It cannot be None, but it can be empty which is practically the same, so let's handle
-    // this in the future to have some consistency and use None() over Some("")
-    description: String,
+    /// The short description of the repository, `None` if empty
+    #[serde(deserialize_with = "empty_string_as_none")]
+    description: Option<String>,
 
+    #[serde(alias = "isPrivate")]
     is_private: bool,
 
+    #[serde(alias = "starCount", deserialize_with = "deserialize_number_or_string")]
     star_count: usize,
 
+    #[serde(alias = "pullCount", deserialize_with = "deserialize_number_or_string")]
     pull_count: usize,
 
+    #[serde(alias = "lastUpdated")]
     last_updated: DateTime<Utc>,
 
+    #[serde(alias = "lastModified")]
     last_modified: DateTime<Utc>,
 
+    #[serde(alias = "dateRegistered")]
     date_registered: DateTime<Utc>,
 
-    // TODO: same as in `description`
-    affiliation: String,
+    /// The affiliation of the repository, `None` if empty
+    #[serde(deserialize_with = "empty_string_as_none")]
+    affiliation: Option<String>,
 
+    #[serde(alias = "mediaTypes")]
     media_types: Vec<String>,
 
+    #[serde(alias = "contentTypes")]
     content_types: Vec<String>,
 
     categories: Vec<Category>,
 
-    /// The size of the virtual image in bytes
+    /// The size of the virtual image in bytes, missing on some listings in which case it's zero
+    #[serde(alias = "storageSize", default)]
     storage_size: u64,
+
+    /// Whether the repository is built automatically, only present on some listings e.g. search
+    #[serde(alias = "isAutomated", default)]
+    is_automated: Option<bool>,
+
+    /// Whether the repository is an official image, only present on some listings e.g. search
+    #[serde(alias = "isOfficial", default)]
+    is_official: Option<bool>,
+}
+
+impl Repository {
+    /// Returns the name of the repository
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the namespace i.e. user or organization where the repository lives in
+    #[inline]
+    pub fn namespace(&self) -> &str {
+        &self.namespace
+    }
+
+    /// Returns the type of repository e.g. "image"
+    #[inline]
+    pub fn repository_type(&self) -> &str {
+        &self.repository_type
+    }
+
+    /// Returns the status code of the repository
+    #[inline]
+    pub fn status(&self) -> usize {
+        self.status
+    }
+
+    /// Returns the description of the status of the repository e.g. "active"
+    #[inline]
+    pub fn status_description(&self) -> &str {
+        &self.status_description
+    }
+
+    /// Returns the short description of the repository, if any
+    #[inline]
+    pub fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    /// Returns the number of stars of the repository
+    #[inline]
+    pub fn star_count(&self) -> usize {
+        self.star_count
+    }
+
+    /// Returns the number of pulls of the repository
+    #[inline]
+    pub fn pull_count(&self) -> usize {
+        self.pull_count
+    }
+
+    /// Returns when the repository was last updated
+    #[inline]
+    pub fn last_updated(&self) -> DateTime<Utc> {
+        self.last_updated
+    }
+
+    /// Returns when the repository was last modified
+    #[inline]
+    pub fn last_modified(&self) -> DateTime<Utc> {
+        self.last_modified
+    }
+
+    /// Returns when the repository was registered
+    #[inline]
+    pub fn date_registered(&self) -> DateTime<Utc> {
+        self.date_registered
+    }
+
+    /// Returns the affiliation of the repository, if any
+    #[inline]
+    pub fn affiliation(&self) -> Option<&str> {
+        self.affiliation.as_deref()
+    }
+
+    /// Returns the categories the repository is assigned to
+    #[inline]
+    pub fn categories(&self) -> &[Category] {
+        &self.categories
+    }
+
+    /// Returns the size of the virtual image in bytes
+    #[inline]
+    pub fn storage_size(&self) -> u64 {
+        self.storage_size
+    }
+
+    /// Returns whether the repository is private
+    #[inline]
+    pub fn is_private(&self) -> bool {
+        self.is_private
+    }
+
+    /// Returns the media types of the manifests and configs within the repository e.g.
+    /// "application/vnd.oci.image.index.v1+json"
+    #[inline]
+    pub fn media_types(&self) -> &[String] {
+        &self.media_types
+    }
+
+    /// Returns the kinds of content within the repository e.g. "image" or "plugin"
+    #[inline]
+    pub fn content_types(&self) -> &[String] {
+        &self.content_types
+    }
+
+    /// Returns the kinds of content within the repository as `ContentType` values
+    pub fn content_type_kinds(&self) -> Vec<ContentType> {
+        self.content_types
+            .iter()
+            .map(|content_type| ContentType::from(content_type.as_str()))
+            .collect()
+    }
+
+    /// Returns whether the repository holds the given kind of content
+    pub fn has_content_type(&self, content_type: &ContentType) -> bool {
+        self.content_types
+            .iter()
+            .any(|value| value == content_type.as_str())
+    }
+
+    /// Returns whether the repository is automated, if reported by the Docker Hub
+    #[inline]
+    pub fn is_automated(&self) -> Option<bool> {
+        self.is_automated
+    }
+
+    /// Returns whether the repository is an official image, if reported by the Docker Hub
+    #[inline]
+    pub fn is_official(&self) -> Option<bool> {
+        self.is_official
+    }
+}
+
+/// The kind of content stored within a repository, as reported in its `content_types`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(from = "String", into = "String")]
+pub enum ContentType {
+    /// Container images
+    Image,
+    /// Docker plugins
+    Plugin,
+    /// Any other kind of artifact e.g. Helm charts or Docker extensions
+    Other(String),
+}
+
+impl ContentType {
+    /// Returns the value of the content type as reported by the Docker Hub
+    pub fn as_str(&self) -> &str {
+        match self {
+            ContentType::Image => "image",
+            ContentType::Plugin => "plugin",
+            ContentType::Other(value) => value,
+        }
+    }
+}
+
+impl From<&str> for ContentType {
+    fn from(value: &str) -> Self {
+        match value {
+            "image" => ContentType::Image,
+            "plugin" => ContentType::Plugin,
+            other => ContentType::Other(other.to_string()),
+        }
+    }
+}
+
+impl From<String> for ContentType {
+    fn from(value: String) -> Self {
+        ContentType::from(value.as_str())
+    }
+}
+
+impl From<ContentType> for String {
+    fn from(value: ContentType) -> Self {
+        value.as_str().to_string()
+    }
+}
+
+impl std::fmt::Display for ContentType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Repositories are identified by their namespace and name, so that the same repository fetched
+/// from overlapping queries (e.g. a search and a listing) can be deduplicated
+impl PartialEq for Repository {
+    fn eq(&self, other: &Self) -> bool {
+        self.namespace == other.namespace && self.name == other.name
+    }
+}
+
+impl Eq for Repository {}
+
+impl Hash for Repository {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.namespace.hash(state);
+        self.name.hash(state);
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Eq)]
+pub struct ContentSummary {
+    /// The distinct media types within the repository, sorted alphabetically
+    pub media_types: BTreeSet<String>,
+
+    /// The distinct content types within the repository e.g. "image", sorted alphabetically
+    pub content_types: BTreeSet<String>,
+}
+
+impl ContentSummary {
+    /// Returns whether the repository holds container images
+    pub fn has_images(&self) -> bool {
+        self.content_types.contains("image")
+    }
+
+    /// Returns whether the repository holds Docker plugins
+    pub fn has_plugins(&self) -> bool {
+        self.content_types.contains("plugin")
+    }
+
+    /// Returns whether the repository holds any other kind of artifact e.g. Helm charts
+    pub fn has_other(&self) -> bool {
+        self.content_types
+            .iter()
+            .any(|content_type| !matches!(content_type.as_str(), "image" | "plugin"))
+    }
+}
+
+impl From<&Repository> for ContentSummary {
+    fn from(repository: &Repository) -> Self {
+        Self {
+            media_types: repository.media_types.iter().cloned().collect(),
+            content_types: repository.content_types.iter().cloned().collect(),
+        }
+    }
+}
+
+/// A user that starred a repository, as returned by the stargazers endpoint
+#[derive(Deserialize)]
+struct Stargazer {
+    /// The username of the user
+    user: String,
+}
+
+/// The maximum difference between the repository and the newest tag timestamps that's still
+/// considered consistent, as the repository is also updated on e.g. description changes
+pub const FRESHNESS_TOLERANCE: TimeDelta = TimeDelta::hours(24);
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct Freshness {
+    /// The `last_updated` timestamp reported for the repository itself
+    pub repository_last_updated: DateTime<Utc>,
+
+    /// The most recent push time across all the tags, if any tag has been pushed
+    pub newest_tag_pushed: Option<DateTime<Utc>>,
+
+    /// Whether both timestamps differ by more than `FRESHNESS_TOLERANCE`, or there's no pushed
+    /// tag at all; in which case the newest tag push time should be trusted over the former
+    pub diverged: bool,
+}
+
+impl Freshness {
+    /// Creates a new `Freshness` flagging whether the provided timestamps diverge
+    pub fn new(
+        repository_last_updated: DateTime<Utc>,
+        newest_tag_pushed: Option<DateTime<Utc>>,
+    ) -> Self {
+        let diverged = newest_tag_pushed
+            .is_none_or(|pushed| (repository_last_updated - pushed).abs() > FRESHNESS_TOLERANCE);
+        Self {
+            repository_last_updated,
+            newest_tag_pushed,
+            diverged,
+        }
+    }
+}
+
+/// The number of tags probed anonymously by `repository_visibility_summary`
+pub const VISIBILITY_SAMPLE_SIZE: usize = 10;
+
+/// How many of the probed tags are accessible anonymously
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub enum TagAccessibility {
+    /// All the probed tags are accessible anonymously
+    All,
+
+    /// Only some of the probed tags are accessible anonymously
+    Some,
+
+    /// None of the probed tags are accessible anonymously, or there are no tags
+    None,
+}
+
+impl TagAccessibility {
+    /// Classifies the accessibility given the number of `accessible` tags out of the `probed`
+    fn from_counts(accessible: usize, probed: usize) -> Self {
+        match accessible {
+            0 => TagAccessibility::None,
+            n if n == probed => TagAccessibility::All,
+            _ => TagAccessibility::Some,
+        }
+    }
 }
 
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct VisibilitySummary {
+    /// Whether the repository itself is private
+    pub is_private: bool,
+
+    /// How many of the probed tags are accessible anonymously
+    pub anonymous_tags: TagAccessibility,
+
+    /// The number of tags probed anonymously, at most `VISIBILITY_SAMPLE_SIZE`
+    pub probed: usize,
+}
+
+/// Setting the categories or the visibility of a repository to a given value is idempotent, so
+/// those requests are safe to retry
+const IDEMPOTENT: SendOptions = SendOptions {
+    idempotent: true,
+    max_retries: None,
+};
+
+/// The number of most recently updated tags included by `inspect`
+pub const INSPECT_TAGS: usize = 5;
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RepositoryInspection {
+    /// The metadata of the repository
+    pub repository: Repository,
+
+    /// The most recently updated tags, up to `INSPECT_TAGS`, including their images i.e. the
+    /// platforms and sizes
+    pub recent_tags: Vec<Tag>,
+}
+
+/// Report of a bulk operation over a set of repositories, containing the names of the
+/// repositories for which the operation succeeded, and a warning per failed repository
+pub type BulkReport = Outcome<Vec<String>>;
+
 impl DockerHubClient {
     /// List all the repositories under a given org or username on the Docker Hub
     ///
@@ -62,22 +449,476 @@ impl DockerHubClient {
     /// Note that if the repository is private but the provided token has access to it,
     /// then the repositories will be listed, otherwise only the public ones (if any)
     /// will be listed.
-    pub async fn list_repositories(&self, org: &str) -> anyhow::Result<Vec<Repository>> {
+    ///
+    /// The `org` can either be a `Namespace::Org`, a `Namespace::User`, or a plain string,
+    /// in which case the namespace is resolved by first trying the organization endpoint
+    /// and then falling back to the user endpoint if the org is not found. When the `org` is
+    /// omitted i.e. `None` or `Namespace::Me`, the personal repositories of the authenticated
+    /// user are listed instead.
+    pub async fn list_repositories(
+        &self,
+        org: impl Into<Namespace>,
+    ) -> Result<Vec<Repository>, HubError> {
+        match org.into() {
+            Namespace::Org(org) => self.list_org_repositories(&org).await,
+            Namespace::User(user) => self.list_user_repositories(&user).await,
+            Namespace::Auto(name) => match self.list_org_repositories(&name).await {
+                Err(e) if matches!(e.inner(), HubError::NotFound(_)) => {
+                    self.list_user_repositories(&name).await
+                }
+                result => result,
+            },
+            Namespace::Me => {
+                let user = self
+                    .whoami()
+                    .await
+                    .context("resolving the authenticated user failed")?;
+                self.list_user_repositories(&user).await
+            }
+        }
+    }
+
+    async fn list_org_repositories(&self, org: &str) -> Result<Vec<Repository>, HubError> {
         let url = self
             .url
             .join(&format!("v2/namespaces/{}/repositories", org)) // For some reason the endpoint `v2/repositories/{}` works seamlessly
             .context("failed formatting the url with the provided org")?;
 
-        fetch_with_pagination::<Repository>(&self.client, &url)
+        fetch_with_pagination::<Repository>(self, &url).await
+    }
+
+    async fn list_user_repositories(&self, user: &str) -> Result<Vec<Repository>, HubError> {
+        let url = self
+            .url
+            .join(&format!("v2/repositories/{}/", user))
+            .context("failed formatting the url with the provided username")?;
+
+        fetch_with_pagination::<Repository>(self, &url).await
+    }
+
+    /// Same as `list_repositories` but for the default namespace of the client
+    pub async fn list_repositories_in(&self) -> Result<Vec<Repository>, HubError> {
+        self.list_repositories(self.require_default_namespace()?)
             .await
+    }
+
+    /// Same as `get_repository` but for a repository under the default namespace of the client
+    pub async fn get_repository_in(&self, name: &str) -> anyhow::Result<Repository> {
+        self.get_repository(self.require_default_namespace()?, name)
+            .await
+    }
+
+    /// List the `limit` most recently updated repositories under a given org or username
+    ///
+    /// This method lets the Docker Hub sort the repositories by their last update and only
+    /// fetches the first page with `limit` repositories, which is far cheaper than listing all
+    /// the repositories and sorting those on the client side. Note that `limit` is capped to
+    /// the maximum page size allowed by the Docker Hub i.e. `MAX_PAGE_SIZE`.
+    pub async fn recently_updated_repositories(
+        &self,
+        org: &str,
+        limit: usize,
+    ) -> anyhow::Result<Vec<Repository>> {
+        if limit == 0 {
+            return Ok(Vec::new());
+        }
+
+        let mut url = self
+            .url
+            .join(&format!("v2/namespaces/{}/repositories", org))
+            .context("failed formatting the url with the provided org")?;
+        url.query_pairs_mut()
+            .append_pair("ordering", "last_updated");
+
+        fetch::<ApiResult<Repository>>(self, &url, Some(1), Some(limit))
+            .await
+            .map(ApiResult::into_results)
             .context("fetching the provided url failed")
     }
+
+    /// List all the repositories under the personal namespace of the authenticated user
+    ///
+    /// This method resolves the username of the authenticated user via `whoami`, so that there's
+    /// no need to hardcode or look it up before listing the own repositories.
+    pub async fn my_repositories(&self) -> Result<Vec<Repository>, HubError> {
+        self.list_repositories(Namespace::Me).await
+    }
+
+    /// List all the repositories under a given org or username on the Docker Hub, ensuring that
+    /// each of those has its storage size populated
+    ///
+    /// Some listings don't include the `storage_size` of the repositories, so this method fetches
+    /// each repository with a missing (or zero) storage size from the single repository endpoint,
+    /// concurrently and with a bounded concurrency; so that the storage reports are accurate
+    /// regardless of the listing endpoint used. The repositories that cannot be fetched are
+    /// reported back as warnings rather than failing the whole listing.
+    pub async fn list_repositories_with_storage(
+        &self,
+        org: impl Into<Namespace>,
+    ) -> Result<Outcome<Vec<Repository>>, HubError> {
+        let repositories = self.list_repositories(org).await?;
+
+        let results = stream::iter(repositories)
+            .map(|repository| async move {
+                if repository.storage_size > 0 {
+                    return (repository.name.clone(), Ok(repository));
+                }
+                let result = self
+                    .get_repository(&repository.namespace, &repository.name)
+                    .await
+                    .with_context(|| {
+                        format!(
+                            "fetching the storage size of {}/{} failed",
+                            repository.namespace, repository.name
+                        )
+                    });
+                (repository.name, result)
+            })
+            .buffered(self.max_concurrent_requests)
+            .collect::<Vec<_>>()
+            .await;
+
+        let mut outcome = Outcome::<Vec<Repository>>::default();
+        for (name, result) in results {
+            match result {
+                Ok(repository) => outcome.value.push(repository),
+                Err(error) => outcome
+                    .warnings
+                    .push(Warning::ItemFailed { item: name, error }),
+            }
+        }
+        Ok(outcome)
+    }
+
+    /// Find the repositories whose name contains a given substring under a given org or username
+    ///
+    /// This method relies on the name filter of the organization repositories endpoint, so that
+    /// the filtering happens on the server side without listing all the repositories; whereas if
+    /// the filter is not supported i.e. a 400 or a 404 e.g. for a username, all the repositories
+    /// are listed instead, and any other failure is returned as is. In both cases the
+    /// names are also matched on the client side, case-insensitively, as the server-side filter
+    /// is not guaranteed to be a substring match.
+    pub async fn find_repositories(
+        &self,
+        org: &str,
+        name_substring: &str,
+    ) -> anyhow::Result<Vec<Repository>> {
+        let mut url = self
+            .url
+            .join(&format!("v2/namespaces/{}/repositories", org))
+            .context("failed formatting the url with the provided org")?;
+        url.query_pairs_mut().append_pair("name", name_substring);
+
+        let repositories = match fetch_with_pagination::<Repository>(self, &url).await {
+            Ok(repositories) => repositories,
+            Err(e)
+                if matches!(
+                    e.status(),
+                    Some(StatusCode::BAD_REQUEST | StatusCode::NOT_FOUND)
+                ) =>
+            {
+                self.list_repositories(org).await?
+            }
+            Err(e) => {
+                return Err(anyhow::Error::from(e).context("fetching the provided url failed"))
+            }
+        };
+
+        let name_substring = name_substring.to_lowercase();
+        Ok(repositories
+            .into_iter()
+            .filter(|repository| repository.name.to_lowercase().contains(&name_substring))
+            .collect())
+    }
+
+    /// List the repositories under a given org or username that hold a given content type
+    ///
+    /// This method lists all the repositories via `list_repositories` and keeps only the ones
+    /// whose `content_types` contain the provided `content_type` e.g. "image" or "plugin", so
+    /// that tooling managing container images only can exclude plugin or extension repositories.
+    pub async fn list_repositories_by_content_type(
+        &self,
+        org: &str,
+        content_type: &str,
+    ) -> Result<Vec<Repository>, HubError> {
+        let content_type = ContentType::from(content_type);
+        Ok(self
+            .list_repositories(org)
+            .await?
+            .into_iter()
+            .filter(|repository| repository.has_content_type(&content_type))
+            .collect())
+    }
+
+    /// Get a given repository on the Docker Hub
+    ///
+    /// This method retrieves the repository `name` under the given `org`, including its
+    /// metadata e.g. the description, the storage size, or the last update time. When the
+    /// repository doesn't exist (or it's private and the token has no access to it), the error
+    /// states that the repository was not found, while the `HubError::NotFound` remains
+    /// accessible via `downcast_ref`.
+    ///
+    /// The `org` can be left empty to provide a full reference via `name` instead, where the
+    /// official images can be referenced bare e.g. "nginx" for "library/nginx".
+    pub async fn get_repository(&self, org: &str, name: &str) -> anyhow::Result<Repository> {
+        let (org, name) = resolve_repository(org, name);
+        let url = self
+            .url
+            .join(&format!("v2/namespaces/{}/repositories/{}", org, name))
+            .context("failed formatting the url with the provided org and repository")?;
+
+        match fetch::<Repository>(self, &url, None, None).await {
+            Ok(repository) => Ok(repository),
+            Err(e) if matches!(e.inner(), HubError::NotFound(_)) => {
+                Err(anyhow::Error::from(e).context(format!("repository {org}/{name} not found")))
+            }
+            Err(e) => Err(anyhow::Error::from(e).context("fetching the provided url failed")),
+        }
+    }
+
+    /// List the usernames of the users that starred a given repository on the Docker Hub
+    ///
+    /// Note that the provided token needs to have owner or admin access to the repository, as
+    /// otherwise the Docker Hub responds with a 403 which is reported back as is i.e. as a
+    /// `HubError::Forbidden`, so that the callers can match on it.
+    pub async fn list_stargazers(&self, org: &str, name: &str) -> Result<Vec<String>, HubError> {
+        let (org, name) = resolve_repository(org, name);
+        let url = self
+            .url
+            .join(&format!("v2/repositories/{}/{}/stargazers/", org, name))
+            .context("failed formatting the url with the provided org and repository")?;
+
+        let stargazers = fetch_with_pagination::<Stargazer>(self, &url).await?;
+        Ok(stargazers
+            .into_iter()
+            .map(|stargazer| stargazer.user)
+            .collect())
+    }
+
+    /// Inspect a given repository on the Docker Hub
+    ///
+    /// This method fetches the metadata of the repository and its most recently updated tags
+    /// concurrently, aggregating those into a single struct; which provides everything needed
+    /// for a rich inspect view e.g. on a CLI, with a single call.
+    pub async fn inspect(&self, org: &str, name: &str) -> anyhow::Result<RepositoryInspection> {
+        let (repository, recent_tags) = futures::try_join!(
+            self.get_repository(org, name),
+            self.latest_tags(org, name, INSPECT_TAGS)
+        )
+        .context("fetching the repository and its tags failed")?;
+
+        Ok(RepositoryInspection {
+            repository,
+            recent_tags,
+        })
+    }
+
+    /// Summarize the kinds of content within a given repository on the Docker Hub
+    ///
+    /// This method returns the distinct media types and content types of the repository, which
+    /// is useful for tooling that only wants to process e.g. the image repositories.
+    pub async fn repository_content_summary(
+        &self,
+        org: &str,
+        name: &str,
+    ) -> anyhow::Result<ContentSummary> {
+        let repository = self.get_repository(org, name).await?;
+        Ok(ContentSummary::from(&repository))
+    }
+
+    /// Compare the repository-level update time against the newest tag push time
+    ///
+    /// The `last_updated` of a repository is also bumped on metadata changes e.g. when the
+    /// description is edited, so it can differ from the time when the newest tag was pushed.
+    /// This method returns both, flagging when those diverge significantly, in which case the
+    /// newest tag push time is the one to trust to tell whether the repository is active.
+    pub async fn repository_freshness(&self, org: &str, name: &str) -> anyhow::Result<Freshness> {
+        let (repository, activity) = futures::try_join!(
+            self.get_repository(org, name),
+            self.repository_activity(org, name)
+        )
+        .context("fetching the repository and its tags failed")?;
+
+        Ok(Freshness::new(
+            repository.last_updated,
+            activity.last_pushed,
+        ))
+    }
+
+    /// Summarize the visibility of a given repository and of its tags on the Docker Hub
+    ///
+    /// This method returns the repository-level privacy, plus whether all, some, or none of its
+    /// tags are accessible anonymously; which is checked by reading the most recently updated
+    /// tags (up to `VISIBILITY_SAMPLE_SIZE`) with a separate client without any token, but
+    /// otherwise configured the same. Only the tags failing with either a 401, a 403, or a 404
+    /// are counted as not accessible, whereas any other failure is returned.
+    pub async fn repository_visibility_summary(
+        &self,
+        org: &str,
+        name: &str,
+    ) -> anyhow::Result<VisibilitySummary> {
+        let (repository, tags) = futures::try_join!(
+            self.get_repository(org, name),
+            self.latest_tags(org, name, VISIBILITY_SAMPLE_SIZE)
+        )
+        .context("fetching the repository and its tags failed")?;
+
+        let anonymous = self.without_token();
+
+        let results: Vec<anyhow::Result<Tag>> = stream::iter(&tags)
+            .map(|tag| anonymous.read_tag(org, name, tag.name()))
+            .buffer_unordered(self.max_concurrent_requests)
+            .collect()
+            .await;
+
+        let mut accessible = 0;
+        for result in results {
+            match result {
+                Ok(_) => accessible += 1,
+                Err(e) => match e.downcast_ref::<HubError>().and_then(HubError::status) {
+                    Some(
+                        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN | StatusCode::NOT_FOUND,
+                    ) => {}
+                    _ => return Err(e.context("reading the tags anonymously failed")),
+                },
+            }
+        }
+
+        Ok(VisibilitySummary {
+            is_private: repository.is_private,
+            anonymous_tags: TagAccessibility::from_counts(accessible, tags.len()),
+            probed: tags.len(),
+        })
+    }
+
+    /// Set the categories of a given repository on the Docker Hub
+    ///
+    /// This method replaces the categories of the repository `name` under the given `org` with
+    /// the ones provided via their `slugs` e.g. "machine-learning-and-ai", which are validated
+    /// against the categories available on the Docker Hub before sending the request. Returns
+    /// the updated repository including its new categories. The request is idempotent, so it's
+    /// retried on transient failures.
+    pub async fn set_repository_categories(
+        &self,
+        org: &str,
+        name: &str,
+        slugs: &[&str],
+    ) -> anyhow::Result<Repository> {
+        let categories = self
+            .categories()
+            .await
+            .context("fetching the available categories failed")?;
+        for slug in slugs {
+            if !categories.iter().any(|category| category.slug == *slug) {
+                anyhow::bail!("category `{slug}` is not available on the docker hub");
+            }
+        }
+
+        let (org, name) = resolve_repository(org, name);
+        let url = self
+            .url
+            .join(&format!("v2/repositories/{}/{}/", org, name))
+            .context("failed formatting the url with the provided org and repository")?;
+
+        let body = json!({ "categories": slugs });
+        send_with_options::<_, Repository>(self, Method::PATCH, &url, &body, &IDEMPOTENT)
+            .await
+            .context("updating the repository categories failed")
+    }
+
+    /// List all the categories available on the Docker Hub
+    ///
+    /// This method lists the categories that can be assigned to the repositories on the
+    /// Docker Hub; note that those are cached on the client after the first call, since the
+    /// available categories rarely change.
+    pub async fn list_categories(&self) -> Result<Vec<Category>, HubError> {
+        Ok(self.categories().await?.to_vec())
+    }
+
+    /// Returns the categories available on the Docker Hub, fetching those only on the first call
+    async fn categories(&self) -> anyhow::Result<&[Category]> {
+        let categories = self
+            .categories
+            .get_or_try_init(|| async {
+                let url = self
+                    .url
+                    .join("v2/categories")
+                    .context("failed formatting the categories url")?;
+
+                fetch::<Vec<Category>>(self, &url, None, None)
+                    .await
+                    .context("fetching the provided url failed")
+            })
+            .await?;
+        Ok(categories)
+    }
+
+    /// Set the visibility of a set of repositories under a given org or username on the Docker Hub
+    ///
+    /// This method updates the visibility of each of the repositories in `names` under the given
+    /// `org` to either private or public via the `private` argument, sending the requests
+    /// concurrently but with a bounded parallelism. The per-repository failures are reported
+    /// back as warnings within the `BulkReport` rather than failing on the first error. The
+    /// requests are idempotent, so those are retried on transient failures. When `dry_run` is
+    /// set, no request is sent and the report contains the repositories that would be updated.
+    pub async fn set_visibility_bulk(
+        &self,
+        org: &str,
+        names: &[&str],
+        private: bool,
+        dry_run: bool,
+    ) -> anyhow::Result<BulkReport> {
+        let mut urls = Vec::with_capacity(names.len());
+        for name in names {
+            let (org, repository) = resolve_repository(org, name);
+            let url = self
+                .url
+                .join(&format!("v2/repositories/{}/{}/", org, repository))
+                .context("failed formatting the url with the provided org and repository")?;
+            urls.push((name.to_string(), url));
+        }
+
+        let mut report = BulkReport::default();
+        if dry_run {
+            report.value = urls.into_iter().map(|(name, _)| name).collect();
+            return Ok(report);
+        }
+
+        let body = json!({ "is_private": private });
+        let results = stream::iter(urls)
+            .map(|(name, url)| {
+                let body = &body;
+                async move {
+                    let result =
+                        send_with_options::<_, Value>(self, Method::PATCH, &url, body, &IDEMPOTENT)
+                            .await
+                            .context("updating the repository visibility failed");
+                    (name, result)
+                }
+            })
+            .buffer_unordered(self.max_concurrent_requests)
+            .collect::<Vec<_>>()
+            .await;
+
+        for (name, result) in results {
+            match result {
+                Ok(_) => report.value.push(name),
+                Err(error) => report
+                    .warnings
+                    .push(Warning::ItemFailed { item: name, error }),
+            }
+        }
+        Ok(report)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use serde_json::json;
+    use std::collections::HashSet;
+
+    use crate::tests::repository_json;
 
     #[test]
     fn test_repository_serde() {
@@ -114,16 +955,372 @@ mod tests {
               "slug": "developer-tools"
             }
           ],
-          "storage_size": 662988133055 as u64,
+          "storage_size": 662988133055_u64,
         });
 
         let repository = serde_json::from_value::<Repository>(value)
             .context("failed to deserialize the repository payload")
             .unwrap();
 
+        assert_eq!(repository.is_automated(), None);
+        assert_eq!(repository.is_official(), None);
+
+        assert_eq!(repository.affiliation(), None);
+        assert_eq!(
+            repository.description(),
+            Some("The easiest way to get up and running with large language models.")
+        );
+        assert_eq!(repository.namespace(), "ollama");
+        assert_eq!(repository.pull_count(), 13256501);
+        assert_eq!(repository.star_count(), 1183);
+        assert_eq!(repository.storage_size(), 662988133055);
+        assert_eq!(
+            repository.last_updated().to_rfc3339(),
+            "2025-03-04T04:01:22.754331+00:00"
+        );
+        assert_eq!(repository.categories()[0].slug(), "machine-learning-and-ai");
+
+        let summary = ContentSummary::from(&repository);
+        assert_eq!(summary.media_types.len(), 4);
+        assert!(summary.has_images());
+        assert!(!summary.has_plugins());
+        assert!(!summary.has_other());
+
         println!("{repository:#?}");
     }
 
+    #[test]
+    fn test_repository_serde_camel_case() {
+        let value = json!({
+          "name": "ollama",
+          "namespace": "ollama",
+          "repositoryType": "image",
+          "status": 1,
+          "statusDescription": "active",
+          "description": "The easiest way to get up and running with large language models.",
+          "isPrivate": false,
+          "starCount": 1183,
+          "pullCount": "13256501",
+          "lastUpdated": "2025-03-04T04:01:22.754331Z",
+          "lastModified": "2024-10-16T13:48:34.145251Z",
+          "dateRegistered": "2023-06-29T23:27:34.326426Z",
+          "affiliation": "",
+          "mediaTypes": [
+            "application/vnd.oci.image.index.v1+json"
+          ],
+          "contentTypes": [
+            "image"
+          ],
+          "categories": [],
+          "storageSize": 662988133055_u64,
+          "isOfficial": false
+        });
+
+        let repository = serde_json::from_value::<Repository>(value)
+            .context("failed to deserialize the camel case repository payload")
+            .unwrap();
+
+        assert_eq!(repository.storage_size(), 662988133055);
+        assert_eq!(repository.is_official(), Some(false));
+
+        println!("{repository:#?}");
+    }
+
+    #[test]
+    fn test_content_type() {
+        assert_eq!(ContentType::from("image"), ContentType::Image);
+        assert_eq!(ContentType::from("plugin"), ContentType::Plugin);
+        assert_eq!(
+            ContentType::from("extension"),
+            ContentType::Other("extension".to_string())
+        );
+        assert_eq!(ContentType::Other("helm".to_string()).to_string(), "helm");
+
+        let content_types: Vec<ContentType> =
+            serde_json::from_value(json!(["image", "plugin", "helm"])).unwrap();
+        assert_eq!(
+            serde_json::to_value(&content_types).unwrap(),
+            json!(["image", "plugin", "helm"])
+        );
+    }
+
+    #[test]
+    fn test_tag_accessibility_from_counts() {
+        assert_eq!(TagAccessibility::from_counts(3, 3), TagAccessibility::All);
+        assert_eq!(TagAccessibility::from_counts(1, 3), TagAccessibility::Some);
+        assert_eq!(TagAccessibility::from_counts(0, 3), TagAccessibility::None);
+        assert_eq!(TagAccessibility::from_counts(0, 0), TagAccessibility::None);
+    }
+
+    #[test]
+    fn test_freshness() {
+        let updated = DateTime::from_timestamp(1741600000, 0).unwrap();
+
+        let freshness = Freshness::new(updated, Some(updated - TimeDelta::hours(1)));
+        assert!(!freshness.diverged);
+
+        let freshness = Freshness::new(updated, Some(updated - TimeDelta::days(30)));
+        assert!(freshness.diverged);
+
+        let freshness = Freshness::new(updated, None);
+        assert!(freshness.diverged);
+    }
+
+    #[test]
+    fn test_repository_serde_with_flags() {
+        let mut value = repository_json("ubuntu");
+        value["namespace"] = json!("library");
+        value["is_automated"] = json!(false);
+        value["is_official"] = json!(true);
+
+        let repository = serde_json::from_value::<Repository>(value)
+            .context("failed to deserialize the repository payload")
+            .unwrap();
+
+        assert_eq!(repository.is_automated(), Some(false));
+        assert_eq!(repository.is_official(), Some(true));
+    }
+
+    #[test]
+    fn test_repository_serde_with_string_counts() {
+        let mut value = repository_json("ollama");
+        value["star_count"] = json!("1183");
+        value["pull_count"] = json!("13256501");
+
+        let repository = serde_json::from_value::<Repository>(value)
+            .context("failed to deserialize the repository payload")
+            .unwrap();
+
+        assert_eq!(repository.star_count, 1183);
+        assert_eq!(repository.pull_count, 13256501);
+    }
+
+    #[test]
+    fn test_repository_identity() {
+        let repository = |namespace: &str, name: &str, pull_count: usize| {
+            let mut value = repository_json(name);
+            value["namespace"] = json!(namespace);
+            value["pull_count"] = json!(pull_count);
+            serde_json::from_value::<Repository>(value).unwrap()
+        };
+
+        assert_eq!(
+            repository("ollama", "ollama", 1),
+            repository("ollama", "ollama", 2)
+        );
+        assert_ne!(
+            repository("ollama", "ollama", 1),
+            repository("ollama", "quantize", 1)
+        );
+
+        let repositories = HashSet::from([
+            repository("ollama", "ollama", 1),
+            repository("ollama", "ollama", 2),
+            repository("library", "ollama", 1),
+        ]);
+        assert_eq!(repositories.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_list_repositories_auto_fallback() {
+        use std::sync::atomic::Ordering;
+
+        use crate::tests::{http_response, mock_server};
+
+        // The user endpoint is only tried when the org is not found
+        let empty = http_response(
+            "200 OK",
+            &[("Content-Type", "application/json".to_string())],
+            r#"{"count": 0, "next": null, "previous": null, "results": []}"#,
+        );
+        let (url, requests) =
+            mock_server(vec![http_response("404 Not Found", &[], ""), empty]).await;
+        let client = DockerHubClient::builder().base_url(url).build().unwrap();
+        assert!(client.list_repositories("ollama").await.unwrap().is_empty());
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+
+        // Whereas any other failure e.g. a rate limit is returned as is
+        let (url, requests) =
+            mock_server(vec![http_response("429 Too Many Requests", &[], "")]).await;
+        let client = DockerHubClient::builder()
+            .base_url(url)
+            .max_retries(0)
+            .build()
+            .unwrap();
+        let error = client.list_repositories("ollama").await.unwrap_err();
+        assert!(matches!(error.inner(), HubError::RateLimited { .. }));
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_list_repositories_with_storage() {
+        use crate::tests::{http_response, json_response, mock_server, repository_json};
+
+        let repository = |name: &str, storage_size: u64| {
+            let mut value = repository_json(name);
+            value["storage_size"] = json!(storage_size);
+            value
+        };
+
+        // The repositories are fetched one after the other, so that the responses are served
+        // in order: the listing, and then the storage of each repository with a missing one
+        let listing = json!({
+            "count": 3,
+            "next": null,
+            "previous": null,
+            "results": [repository("ollama", 0), repository("quantize", 0), repository("gguf", 42)],
+        });
+        let (url, _) = mock_server(vec![
+            json_response(&listing),
+            json_response(&repository("ollama", 1024)),
+            http_response("403 Forbidden", &[], ""),
+        ])
+        .await;
+        let client = DockerHubClient::builder()
+            .base_url(url)
+            .max_concurrent_requests(1)
+            .build()
+            .unwrap();
+
+        let outcome = client
+            .list_repositories_with_storage("ollama")
+            .await
+            .unwrap();
+        let sizes: Vec<_> = outcome
+            .value
+            .iter()
+            .map(|repository| (repository.name.as_str(), repository.storage_size()))
+            .collect();
+        assert_eq!(sizes, vec![("ollama", 1024), ("gguf", 42)]);
+
+        let [Warning::ItemFailed { item, error }] = outcome.warnings.as_slice() else {
+            panic!("expected a single warning, got {:?}", outcome.warnings);
+        };
+        assert_eq!(item, "quantize");
+        assert!(matches!(
+            error.downcast_ref::<HubError>().map(HubError::inner),
+            Some(HubError::Forbidden(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_list_stargazers_forbidden() {
+        use crate::tests::{http_response, mock_server};
+
+        let (url, _) = mock_server(vec![http_response("403 Forbidden", &[], "")]).await;
+        let client = DockerHubClient::builder().base_url(url).build().unwrap();
+
+        let error = client
+            .list_stargazers("ollama", "ollama")
+            .await
+            .unwrap_err();
+        assert!(matches!(error.inner(), HubError::Forbidden(_)));
+    }
+
+    #[tokio::test]
+    async fn test_set_visibility_bulk_dry_run() {
+        use std::sync::atomic::Ordering;
+
+        use crate::tests::{http_response, mock_server};
+
+        let (url, requests) = mock_server(vec![http_response(
+            "200 OK",
+            &[("Content-Type", "application/json".to_string())],
+            "{}",
+        )])
+        .await;
+        let client = DockerHubClient::builder().base_url(url).build().unwrap();
+
+        // The dry run reports the planned repositories without sending any request
+        let report = client
+            .set_visibility_bulk("ollama", &["ollama", "quantize"], true, true)
+            .await
+            .unwrap();
+        assert_eq!(report.value, vec!["ollama", "quantize"]);
+        assert!(report.warnings.is_empty());
+        assert_eq!(requests.load(Ordering::SeqCst), 0);
+
+        let mut report = client
+            .set_visibility_bulk("ollama", &["ollama", "quantize"], true, false)
+            .await
+            .unwrap();
+        report.value.sort();
+        assert_eq!(report.value, vec!["ollama", "quantize"]);
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_find_repositories_fallback() {
+        use std::sync::atomic::Ordering;
+
+        use crate::tests::{http_response, mock_server};
+
+        // The unfiltered listing is only used when the filter is not supported
+        let empty = http_response(
+            "200 OK",
+            &[("Content-Type", "application/json".to_string())],
+            r#"{"count": 0, "next": null, "previous": null, "results": []}"#,
+        );
+        let (url, requests) =
+            mock_server(vec![http_response("400 Bad Request", &[], ""), empty]).await;
+        let client = DockerHubClient::builder().base_url(url).build().unwrap();
+        assert!(client
+            .find_repositories("ollama", "llama")
+            .await
+            .unwrap()
+            .is_empty());
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+
+        // Whereas any other failure e.g. an authentication one is returned as is
+        let (url, requests) = mock_server(vec![http_response("401 Unauthorized", &[], "")]).await;
+        let client = DockerHubClient::builder().base_url(url).build().unwrap();
+        let error = client
+            .find_repositories("ollama", "llama")
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<HubError>().map(HubError::inner),
+            Some(HubError::Unauthorized(_))
+        ));
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_get_repository_resolves_official_images() {
+        use crate::tests::{http_response, mock_server};
+
+        let (url, _) = mock_server(vec![http_response("404 Not Found", &[], "")]).await;
+        let client = DockerHubClient::builder().base_url(url).build().unwrap();
+
+        let error = client.get_repository("", "nginx").await.unwrap_err();
+        assert_eq!(error.to_string(), "repository library/nginx not found");
+        assert_eq!(
+            error
+                .downcast_ref::<HubError>()
+                .and_then(HubError::url)
+                .map(url::Url::path),
+            Some("/v2/namespaces/library/repositories/nginx")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_repository_not_found() {
+        use crate::tests::{http_response, mock_server};
+
+        let (url, _) = mock_server(vec![http_response("404 Not Found", &[], "")]).await;
+        let client = DockerHubClient::builder().base_url(url).build().unwrap();
+
+        let error = client
+            .get_repository("ollama", "missing")
+            .await
+            .unwrap_err();
+        assert_eq!(error.to_string(), "repository ollama/missing not found");
+        assert!(matches!(
+            error.downcast_ref::<HubError>(),
+            Some(HubError::NotFound(_))
+        ));
+    }
+
     #[tokio::test]
     async fn test_list_repositories() -> anyhow::Result<()> {
         let pat =
@@ -0,0 +1,257 @@
+use std::fmt;
+use std::future::Future;
+use std::time::Duration;
+
+use crate::clock::Clock;
+
+/// The policy that defines how many times and how often a failed request is retried
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    /// The maximum number of retries after the first attempt, zero meaning no retries
+    pub max_retries: usize,
+
+    /// The delay before the first retry, doubled on each subsequent retry
+    pub initial_backoff: Duration,
+
+    /// The upper bound for the delay between retries, including the ones requested via the
+    /// `RetryError::Retryable::retry_after`
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Returns the delay before the provided retry (starting at zero) with exponential backoff
+    pub fn backoff(&self, retry: usize) -> Duration {
+        let factor = 2_u32.saturating_pow(retry.try_into().unwrap_or(u32::MAX));
+        self.initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+}
+
+/// The error of a single attempt, classified as either retryable or fatal
+#[derive(Debug)]
+pub enum RetryError<E = anyhow::Error> {
+    /// The attempt failed but may succeed if retried e.g. a connection error or a 503, with an
+    /// optional delay to wait for before retrying instead of the backoff from the policy
+    Retryable {
+        error: E,
+        retry_after: Option<Duration>,
+    },
+
+    /// The attempt failed and retrying won't help e.g. a 404
+    Fatal(E),
+}
+
+impl<E> RetryError<E> {
+    /// Creates a retryable error that will be retried after the backoff from the policy
+    pub fn retryable(error: E) -> Self {
+        RetryError::Retryable {
+            error,
+            retry_after: None,
+        }
+    }
+}
+
+/// The last error returned by `retry_with`, along with the number of retries sent before it
+#[derive(Debug)]
+pub struct RetryFailure<E> {
+    /// The error of the last attempt
+    pub error: E,
+
+    /// The number of retries sent after the first attempt
+    pub retries: usize,
+
+    /// Whether the attempts stopped because the retries were exhausted, rather than because
+    /// the last attempt failed with a `RetryError::Fatal` error
+    pub exhausted: bool,
+}
+
+/// Runs the provided async closure, retrying it as per the `policy` while it fails with a
+/// `RetryError::Retryable` error
+///
+/// The closure is called once per attempt to produce the request, so that a new request is
+/// sent on each retry; and its result is returned as soon as it succeeds, fails with a
+/// `RetryError::Fatal` error, or once the retries are exhausted, returning the last error.
+/// The delays between retries are awaited via the provided `clock`; whereas a `retry_after`
+/// longer than the `max_backoff` of the policy is not waited for, returning the error instead.
+pub async fn retry<F, Fut, T>(clock: &dyn Clock, policy: &RetryPolicy, f: F) -> anyhow::Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, RetryError>>,
+{
+    retry_with(clock, policy, f).await.map_err(|failure| {
+        if failure.exhausted {
+            failure
+                .error
+                .context(format!("giving up after {} retries", failure.retries))
+        } else {
+            failure.error
+        }
+    })
+}
+
+/// Same as `retry` but for any error type `E`, returning the last error as a `RetryFailure`
+/// so that the caller can report the retries sent in its own error type
+pub async fn retry_with<F, Fut, T, E>(
+    clock: &dyn Clock,
+    policy: &RetryPolicy,
+    mut f: F,
+) -> Result<T, RetryFailure<E>>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, RetryError<E>>>,
+    E: fmt::Display,
+{
+    let mut retries = 0;
+    loop {
+        match f().await {
+            Ok(value) => return Ok(value),
+            Err(RetryError::Fatal(error)) => {
+                return Err(RetryFailure {
+                    error,
+                    retries,
+                    exhausted: false,
+                })
+            }
+            Err(RetryError::Retryable { error, retry_after }) => {
+                if retries >= policy.max_retries {
+                    return Err(RetryFailure {
+                        error,
+                        retries,
+                        exhausted: true,
+                    });
+                }
+                // A bogus or hostile delay could otherwise stall the request for hours
+                if retry_after.is_some_and(|retry_after| retry_after > policy.max_backoff) {
+                    return Err(RetryFailure {
+                        error,
+                        retries,
+                        exhausted: false,
+                    });
+                }
+                let delay = retry_after.unwrap_or_else(|| policy.backoff(retries));
+                tracing::debug!("retrying in {delay:?} after error: {error:#}");
+                clock.sleep(delay).await;
+                retries += 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use chrono::Utc;
+
+    use crate::clock::MockClock;
+
+    fn policy() -> RetryPolicy {
+        RetryPolicy {
+            max_retries: 3,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(3),
+        }
+    }
+
+    #[test]
+    fn test_backoff() {
+        let policy = RetryPolicy::default();
+        assert_eq!(policy.backoff(0), Duration::from_millis(500));
+        assert_eq!(policy.backoff(1), Duration::from_secs(1));
+        assert_eq!(policy.backoff(3), Duration::from_secs(4));
+        assert_eq!(policy.backoff(10), Duration::from_secs(30));
+        assert_eq!(policy.backoff(usize::MAX), Duration::from_secs(30));
+    }
+
+    #[tokio::test]
+    async fn test_retry_until_success() {
+        let clock = MockClock::new(Utc::now());
+        let attempts = AtomicUsize::new(0);
+        let result = retry(&clock, &policy(), || async {
+            match attempts.fetch_add(1, Ordering::SeqCst) {
+                0 | 1 => Err(RetryError::retryable(anyhow::anyhow!(
+                    "service unavailable"
+                ))),
+                _ => Ok("ok"),
+            }
+        })
+        .await;
+
+        assert_eq!(result.unwrap(), "ok");
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+        assert_eq!(
+            clock.sleeps(),
+            vec![Duration::from_secs(1), Duration::from_secs(2)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_retry_stops_on_fatal() {
+        let clock = MockClock::new(Utc::now());
+        let attempts = AtomicUsize::new(0);
+        let result = retry(&clock, &policy(), || async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Err::<(), _>(RetryError::Fatal(anyhow::anyhow!("not found")))
+        })
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+        assert!(clock.sleeps().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_retry_after_beyond_max_backoff() {
+        let clock = MockClock::new(Utc::now());
+        let attempts = AtomicUsize::new(0);
+        let result = retry(&clock, &policy(), || async {
+            let retry_after = match attempts.fetch_add(1, Ordering::SeqCst) {
+                0 => Duration::from_secs(3),
+                _ => Duration::from_secs(3600),
+            };
+            Err::<(), _>(RetryError::Retryable {
+                error: anyhow::anyhow!("too many requests"),
+                retry_after: Some(retry_after),
+            })
+        })
+        .await;
+
+        assert_eq!(result.unwrap_err().to_string(), "too many requests");
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+        assert_eq!(clock.sleeps(), vec![Duration::from_secs(3)]);
+    }
+
+    #[tokio::test]
+    async fn test_retry_gives_up() {
+        let clock = MockClock::new(Utc::now());
+        let attempts = AtomicUsize::new(0);
+        let result = retry(&clock, &policy(), || async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Err::<(), _>(RetryError::retryable(anyhow::anyhow!("bad gateway")))
+        })
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(attempts.load(Ordering::SeqCst), 4);
+        assert_eq!(
+            clock.sleeps(),
+            vec![
+                Duration::from_secs(1),
+                Duration::from_secs(2),
+                Duration::from_secs(3)
+            ]
+        );
+    }
+}
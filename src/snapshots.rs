@@ -0,0 +1,254 @@
+use std::collections::BTreeMap;
+
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::utils::resolve_repository;
+use crate::DockerHubClient;
+
+/// The tags of a repository and their digests at a point in time
+///
+/// The snapshots are serializable, so that those can be persisted (e.g. as JSON) and compared
+/// across runs via `diff_snapshots` to build an audit trail of the tag changes.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RepoSnapshot {
+    /// The organization or username where the repository lives in
+    pub org: String,
+
+    /// The name of the repository
+    pub repository: String,
+
+    /// When the snapshot was taken
+    pub taken_at: DateTime<Utc>,
+
+    /// The digest of each tag, keyed by the tag name
+    pub tags: BTreeMap<String, String>,
+}
+
+/// The repositories of an organization and their pull counts at a point in time
+///
+/// Similarly to `RepoSnapshot`, those can be persisted periodically and compared via
+/// `diff_pull_counts` to report the growth in pulls, without relying on a time-series endpoint
+/// on the Docker Hub.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct OrgSnapshot {
+    /// The organization or username where the repositories live in
+    pub org: String,
+
+    /// When the snapshot was taken
+    pub taken_at: DateTime<Utc>,
+
+    /// The pull count of each repository, keyed by the repository name
+    pub pull_counts: BTreeMap<String, usize>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RetaggedTag {
+    /// The name of the tag
+    pub name: String,
+
+    /// The digest that the tag pointed to on the old snapshot
+    pub old_digest: String,
+
+    /// The digest that the tag points to on the new snapshot
+    pub new_digest: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Eq)]
+pub struct SnapshotDiff {
+    /// The tags only on the new snapshot as `(name, digest)`, sorted by name
+    pub added: Vec<(String, String)>,
+
+    /// The tags only on the old snapshot as `(name, digest)`, sorted by name
+    pub removed: Vec<(String, String)>,
+
+    /// The tags on both snapshots but pointing to a different digest, sorted by name
+    pub retagged: Vec<RetaggedTag>,
+}
+
+impl SnapshotDiff {
+    /// Returns whether there are no changes between the snapshots
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.retagged.is_empty()
+    }
+}
+
+/// Compares two snapshots of the same repository, returning the added, removed, and retagged
+/// tags from `old` to `new`
+pub fn diff_snapshots(old: &RepoSnapshot, new: &RepoSnapshot) -> SnapshotDiff {
+    let mut diff = SnapshotDiff::default();
+    for (name, digest) in &new.tags {
+        match old.tags.get(name) {
+            None => diff.added.push((name.clone(), digest.clone())),
+            Some(old_digest) if old_digest != digest => diff.retagged.push(RetaggedTag {
+                name: name.clone(),
+                old_digest: old_digest.clone(),
+                new_digest: digest.clone(),
+            }),
+            Some(_) => {}
+        }
+    }
+    for (name, digest) in &old.tags {
+        if !new.tags.contains_key(name) {
+            diff.removed.push((name.clone(), digest.clone()));
+        }
+    }
+    diff
+}
+
+/// Compares two snapshots of the same organization, returning the change in pulls per repository
+/// from `old` to `new` as `(repository, delta)`, sorted by the repository name
+///
+/// The repositories only on the new snapshot are reported with all their pulls as the delta,
+/// whereas the ones only on the old snapshot (i.e. removed since) are not reported.
+pub fn diff_pull_counts(old: &OrgSnapshot, new: &OrgSnapshot) -> Vec<(String, i64)> {
+    new.pull_counts
+        .iter()
+        .map(|(repository, pull_count)| {
+            let old_pull_count = old.pull_counts.get(repository).copied().unwrap_or_default();
+            (
+                repository.clone(),
+                *pull_count as i64 - old_pull_count as i64,
+            )
+        })
+        .collect()
+}
+
+impl DockerHubClient {
+    /// Take a snapshot of the repositories and their pull counts for a given org or username on
+    /// the Docker Hub
+    pub async fn org_snapshot(&self, org: &str) -> anyhow::Result<OrgSnapshot> {
+        let taken_at = self.clock.now();
+        let repositories = self
+            .list_repositories(org)
+            .await
+            .context("listing the repositories failed")?;
+
+        Ok(OrgSnapshot {
+            org: org.to_string(),
+            taken_at,
+            pull_counts: repositories
+                .into_iter()
+                .map(|repository| {
+                    let pull_count = repository.pull_count();
+                    (repository.name, pull_count)
+                })
+                .collect(),
+        })
+    }
+
+    /// Take a snapshot of the tags and their digests for a given repository on the Docker Hub
+    pub async fn snapshot(&self, org: &str, repository: &str) -> anyhow::Result<RepoSnapshot> {
+        let taken_at = self.clock.now();
+        let tags = self
+            .list_tags(org, repository)
+            .await
+            .context("listing the tags failed")?;
+
+        let (org, repository) = resolve_repository(org, repository);
+        Ok(RepoSnapshot {
+            org: org.to_string(),
+            repository: repository.to_string(),
+            taken_at,
+            tags: tags
+                .into_iter()
+                .map(|tag| (tag.name().to_string(), tag.digest().to_string()))
+                .collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(tags: &[(&str, &str)]) -> RepoSnapshot {
+        RepoSnapshot {
+            org: "ollama".to_string(),
+            repository: "ollama".to_string(),
+            taken_at: DateTime::from_timestamp(1741600000, 0).unwrap(),
+            tags: tags
+                .iter()
+                .map(|(name, digest)| (name.to_string(), digest.to_string()))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_diff_snapshots() {
+        let old = snapshot(&[
+            ("0.5.0", "sha256:a"),
+            ("latest", "sha256:a"),
+            ("rc", "sha256:r"),
+        ]);
+        let new = snapshot(&[
+            ("0.5.0", "sha256:a"),
+            ("0.6.0", "sha256:b"),
+            ("latest", "sha256:b"),
+        ]);
+
+        let diff = diff_snapshots(&old, &new);
+        assert_eq!(
+            diff.added,
+            vec![("0.6.0".to_string(), "sha256:b".to_string())]
+        );
+        assert_eq!(
+            diff.removed,
+            vec![("rc".to_string(), "sha256:r".to_string())]
+        );
+        assert_eq!(
+            diff.retagged,
+            vec![RetaggedTag {
+                name: "latest".to_string(),
+                old_digest: "sha256:a".to_string(),
+                new_digest: "sha256:b".to_string(),
+            }]
+        );
+
+        assert!(diff_snapshots(&new, &new).is_empty());
+    }
+
+    #[test]
+    fn test_diff_pull_counts() {
+        let org_snapshot = |pull_counts: &[(&str, usize)]| OrgSnapshot {
+            org: "ollama".to_string(),
+            taken_at: DateTime::from_timestamp(1741600000, 0).unwrap(),
+            pull_counts: pull_counts
+                .iter()
+                .map(|(repository, pull_count)| (repository.to_string(), *pull_count))
+                .collect(),
+        };
+
+        let old = org_snapshot(&[("ollama", 1000), ("quantize", 50), ("removed", 10)]);
+        let new = org_snapshot(&[("added", 5), ("ollama", 1250), ("quantize", 50)]);
+
+        assert_eq!(
+            diff_pull_counts(&old, &new),
+            vec![
+                ("added".to_string(), 5),
+                ("ollama".to_string(), 250),
+                ("quantize".to_string(), 0),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_resolves_official_images() {
+        use crate::tests::{http_response, mock_server};
+
+        let page = r#"{"count": 0, "next": null, "previous": null, "results": []}"#;
+        let (url, _) = mock_server(vec![http_response(
+            "200 OK",
+            &[("Content-Type", "application/json".to_string())],
+            page,
+        )])
+        .await;
+        let client = DockerHubClient::builder().base_url(url).build().unwrap();
+
+        let snapshot = client.snapshot("", "nginx").await.unwrap();
+        assert_eq!(snapshot.org, "library");
+        assert_eq!(snapshot.repository, "nginx");
+        assert!(snapshot.tags.is_empty());
+    }
+}
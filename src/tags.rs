@@ -1,8 +1,23 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::io::Write;
+use std::ops::ControlFlow;
+use std::time::Duration;
+
 use anyhow::Context;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, TimeDelta, Utc};
+use futures::stream::{self, StreamExt};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use url::Url;
 
-use crate::{fetch, fetch_with_pagination, DockerHubClient};
+use crate::error::HubError;
+use crate::utils::resolve_repository;
+use crate::{
+    fetch, fetch_with_budget, fetch_with_options, fetch_with_pagination,
+    fetch_with_pagination_with_options, probe_count, walk_pages, ApiResult, DockerHubClient,
+    Outcome, PaginationOptions, SendOptions, Warning,
+};
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Layer {
@@ -19,12 +34,16 @@ pub struct Image {
     digest: String,
     layers: Option<Vec<Layer>>, // For some reason this shows within the API documentation but
                                 // it's not there so let's keep it until the report is scaled
-    os: Option<String>,  // Either None or "unknown"
+    os: Option<String>, // Either None or "unknown"
+    #[serde(alias = "osFeatures")]
     os_features: String, // Not None, but can be "" if not defined
+    #[serde(alias = "osVersion")]
     os_version: Option<String>,
     size: u64,
     status: String,
+    #[serde(alias = "lastPulled")]
     last_pulled: Option<DateTime<Utc>>,
+    #[serde(alias = "lastPushed")]
     last_pushed: Option<DateTime<Utc>>,
 }
 
@@ -37,45 +56,604 @@ pub struct Tag {
     id: u64,
 
     images: Vec<Image>,
+    #[serde(alias = "lastUpdated")]
     last_updated: Option<DateTime<Utc>>,
+    #[serde(alias = "lastUpdater")]
     last_updater: u64,
+    #[serde(alias = "lastUpdaterUsername")]
     last_updater_username: String,
 
     /// The name of the tag for a given repository in the Docker Hub
     name: String,
 
     repository: u64,
+    #[serde(alias = "fullSize")]
     full_size: u64,
     v2: bool,
+    #[serde(alias = "tagStatus")]
     tag_status: String,
+    #[serde(alias = "tagLastPulled")]
     tag_last_pulled: Option<DateTime<Utc>>,
+    #[serde(alias = "tagLastPushed")]
     tag_last_pushed: Option<DateTime<Utc>>,
+    #[serde(alias = "mediaType")]
     media_type: String,
+    #[serde(alias = "contentType")]
     content_type: String,
     digest: String,
 }
 
+/// The common media types for the tags on the Docker Hub
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MediaType {
+    /// An OCI image index i.e. a multi-platform image
+    OciImageIndex,
+
+    /// An OCI image manifest i.e. a single-platform image
+    OciImageManifest,
+
+    /// A Docker manifest list i.e. a multi-platform image
+    DockerManifestList,
+
+    /// A Docker image manifest i.e. a single-platform image
+    DockerManifest,
+}
+
+impl MediaType {
+    /// Returns the media type string as reported by the Docker Hub
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MediaType::OciImageIndex => "application/vnd.oci.image.index.v1+json",
+            MediaType::OciImageManifest => "application/vnd.oci.image.manifest.v1+json",
+            MediaType::DockerManifestList => {
+                "application/vnd.docker.distribution.manifest.list.v2+json"
+            }
+            MediaType::DockerManifest => "application/vnd.docker.distribution.manifest.v2+json",
+        }
+    }
+}
+
+impl Tag {
+    /// Returns the name of the tag e.g. "latest"
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the size of the tag in bytes i.e. the sum of the sizes of its images
+    pub fn full_size(&self) -> u64 {
+        self.full_size
+    }
+
+    /// Returns the media type of the tag e.g. "application/vnd.oci.image.index.v1+json"
+    pub fn media_type(&self) -> &str {
+        &self.media_type
+    }
+
+    /// Returns the content type of the tag e.g. "image"
+    pub fn content_type(&self) -> &str {
+        &self.content_type
+    }
+
+    /// Returns the digest of the tag i.e. of the manifest or the image index
+    pub fn digest(&self) -> &str {
+        &self.digest
+    }
+
+    /// Returns the username of the Docker Hub user that last updated the tag
+    pub fn last_updater_username(&self) -> &str {
+        &self.last_updater_username
+    }
+
+    /// Returns when the tag was last updated, if reported by the Docker Hub
+    pub fn last_updated(&self) -> Option<DateTime<Utc>> {
+        self.last_updated
+    }
+
+    /// Returns when the tag was last pushed, if reported by the Docker Hub
+    pub fn last_pushed(&self) -> Option<DateTime<Utc>> {
+        self.tag_last_pushed
+    }
+
+    /// Returns when the tag was last pulled, if reported by the Docker Hub
+    pub fn last_pulled(&self) -> Option<DateTime<Utc>> {
+        self.tag_last_pulled
+    }
+
+    /// Returns the images within the tag, one per platform for multi-platform tags
+    pub fn images(&self) -> &[Image] {
+        &self.images
+    }
+}
+
+/// The platform that an image is built for e.g. "linux/arm64/v8"
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Platform {
+    /// The operating system e.g. "linux", or "unknown" if not reported
+    pub os: String,
+
+    /// The CPU architecture e.g. "amd64", or "unknown" if not reported
+    pub architecture: String,
+
+    /// The variant of the CPU architecture e.g. "v8", if any
+    pub variant: Option<String>,
+}
+
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.os, self.architecture)?;
+        if let Some(variant) = &self.variant {
+            write!(f, "/{variant}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Image {
+    /// Returns the CPU architecture of the image e.g. "amd64", or "unknown" if not reported
+    pub fn architecture(&self) -> &str {
+        &self.architecture
+    }
+
+    /// Returns the operating system of the image e.g. "linux", if reported by the Docker Hub
+    pub fn os(&self) -> Option<&str> {
+        self.os.as_deref()
+    }
+
+    /// Returns the CPU variant of the image e.g. "v8", if any
+    pub fn variant(&self) -> Option<&str> {
+        self.variant.as_deref()
+    }
+
+    /// Returns the digest of the image i.e. of its platform-specific manifest
+    pub fn digest(&self) -> &str {
+        &self.digest
+    }
+
+    /// Returns the size of the image in bytes
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Returns when the image was last pushed, if reported by the Docker Hub
+    pub fn last_pushed(&self) -> Option<DateTime<Utc>> {
+        self.last_pushed
+    }
+
+    /// Returns the platform that the image is built for
+    pub fn platform(&self) -> Platform {
+        Platform {
+            os: self.os.clone().unwrap_or_else(|| "unknown".to_string()),
+            architecture: self.architecture.clone(),
+            variant: self.variant.clone().filter(|variant| !variant.is_empty()),
+        }
+    }
+}
+
+/// The record written per tag by `tags_to_jsonl`
+#[derive(Serialize)]
+struct TagRecord<'a> {
+    name: &'a str,
+    digest: &'a str,
+    size: u64,
+    last_pushed: Option<DateTime<Utc>>,
+    platforms: Vec<String>,
+}
+
+impl<'a> From<&'a Tag> for TagRecord<'a> {
+    fn from(tag: &'a Tag) -> Self {
+        Self {
+            name: &tag.name,
+            digest: &tag.digest,
+            size: tag.full_size,
+            last_pushed: tag.tag_last_pushed,
+            platforms: tag
+                .images
+                .iter()
+                .map(|image| image.platform().to_string())
+                .collect(),
+        }
+    }
+}
+
+/// Writes the provided tags into the `writer` as JSON lines i.e. one JSON object per line
+fn write_jsonl(tags: &[Tag], writer: &mut impl Write) -> anyhow::Result<()> {
+    for tag in tags {
+        serde_json::to_writer(&mut *writer, &TagRecord::from(tag))
+            .context("failed serializing the tag")?;
+        writer
+            .write_all(b"\n")
+            .context("failed writing into the provided writer")?;
+    }
+    Ok(())
+}
+
+/// Groups the provided names that are equal case-insensitively, only returning the groups with
+/// more than one name; both the groups and the names within are sorted alphabetically
+fn case_collisions<'a>(names: impl IntoIterator<Item = &'a str>) -> Vec<Vec<String>> {
+    let mut groups = BTreeMap::<String, Vec<String>>::new();
+    for name in names {
+        groups
+            .entry(name.to_lowercase())
+            .or_default()
+            .push(name.to_string());
+    }
+    groups
+        .into_values()
+        .filter(|group| group.len() > 1)
+        .map(|mut group| {
+            group.sort();
+            group
+        })
+        .collect()
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Eq)]
+pub struct RepoActivity {
+    /// The oldest push time across all the tags, if any tag has been pushed
+    pub first_pushed: Option<DateTime<Utc>>,
+
+    /// The most recent push time across all the tags, if any tag has been pushed
+    pub last_pushed: Option<DateTime<Utc>>,
+
+    /// The number of tags within the repository
+    pub tag_count: usize,
+}
+
+/// The polls are already retried by `wait_for_tag` until its timeout, so each of those is sent
+/// only once
+const NO_RETRIES: SendOptions = SendOptions {
+    idempotent: false,
+    max_retries: Some(0),
+};
+
+/// Adds the provided `duration` to the provided `time`, saturating to the maximum time rather
+/// than panicking on overflow e.g. for a `Duration::MAX` timeout
+fn saturating_add(time: DateTime<Utc>, duration: Duration) -> DateTime<Utc> {
+    TimeDelta::from_std(duration)
+        .ok()
+        .and_then(|delta| time.checked_add_signed(delta))
+        .unwrap_or(DateTime::<Utc>::MAX_UTC)
+}
+
 impl DockerHubClient {
     /// List all the tags for a given repository on the Docker Hub
     ///
     /// This method expects both the organization or username via the `org`
     /// argument plus the `repository` name for the repository that the tags
     /// will be listed for.
-    pub async fn list_tags(&self, org: &str, repository: &str) -> anyhow::Result<Vec<Tag>> {
-        let url = self
-            .url
+    pub async fn list_tags(&self, org: &str, repository: &str) -> Result<Vec<Tag>, HubError> {
+        let url = self.tags_url(org, repository)?;
+
+        fetch_with_pagination::<Tag>(self, &url).await
+    }
+
+    /// Same as `list_tags` but for a repository under the default namespace of the client
+    pub async fn list_tags_in(&self, repository: &str) -> Result<Vec<Tag>, HubError> {
+        self.list_tags(self.require_default_namespace()?, repository)
+            .await
+    }
+
+    /// Count the tags for a given repository on the Docker Hub
+    ///
+    /// This method reads the authoritative `count` reported on the first page, requesting a
+    /// single tag to keep the response small. Only when the Docker Hub omits the `count`, the
+    /// tags are counted by probing the pages concurrently until the last one is found, which
+    /// requires about one request per hundred tags, rather than listing all of those.
+    pub async fn count_tags(&self, org: &str, repository: &str) -> anyhow::Result<usize> {
+        let url = self.tags_url(org, repository)?;
+
+        let page = fetch::<Value>(self, &url, Some(1), Some(1))
+            .await
+            .context("fetching the provided url failed")?;
+        if let Some(count) = page.get("count").and_then(Value::as_u64) {
+            return Ok(count as usize);
+        }
+
+        probe_count(self.max_concurrent_requests, |page, page_size| {
+            fetch::<ApiResult<Tag>>(self, &url, Some(page), Some(page_size))
+        })
+        .await
+        .context("probing the tag pages failed")
+    }
+
+    /// List at most `max_items` tags for a given repository on the Docker Hub
+    ///
+    /// This method is the same as `list_tags`, but the pagination stops as soon as `max_items`
+    /// tags are collected, rather than fetching all the tags; which is useful for bounded
+    /// inventory jobs over repositories with thousands of tags.
+    pub async fn list_tags_limited(
+        &self,
+        org: &str,
+        repository: &str,
+        max_items: usize,
+    ) -> Result<Vec<Tag>, HubError> {
+        let url = self.tags_url(org, repository)?;
+
+        let options = PaginationOptions {
+            max_items: Some(max_items),
+            ..Default::default()
+        };
+        fetch_with_pagination_with_options::<Tag>(self, &url, &options).await
+    }
+
+    /// List the tags for a given repository on the Docker Hub sending at most `request_budget`
+    /// page requests
+    ///
+    /// This method pages through the tags one page after the other, and once the budget is
+    /// exhausted returns the tags collected so far along with a `Warning::BudgetExceeded`,
+    /// which carries the cursor to resume from; so that the cost of listing the tags of a
+    /// repository is bounded regardless of its number of tags.
+    pub async fn list_tags_with_budget(
+        &self,
+        org: &str,
+        repository: &str,
+        request_budget: usize,
+    ) -> Result<Outcome<Vec<Tag>>, HubError> {
+        let url = self.tags_url(org, repository)?;
+
+        fetch_with_budget::<Tag>(self, &url, request_budget).await
+    }
+
+    /// List the `n` most recently updated tags for a given repository on the Docker Hub
+    ///
+    /// This method lets the Docker Hub sort the tags by their last update and only fetches the
+    /// first page with `n` tags, which is far cheaper than listing all the tags and sorting
+    /// those on the client side for repositories with thousands of tags. Note that `n` is
+    /// capped to the maximum page size allowed by the Docker Hub i.e. `MAX_PAGE_SIZE`.
+    pub async fn latest_tags(
+        &self,
+        org: &str,
+        repository: &str,
+        n: usize,
+    ) -> anyhow::Result<Vec<Tag>> {
+        if n == 0 {
+            return Ok(Vec::new());
+        }
+
+        let mut url = self.tags_url(org, repository)?;
+        url.query_pairs_mut()
+            .append_pair("ordering", "last_updated");
+
+        fetch::<ApiResult<Tag>>(self, &url, Some(1), Some(n))
+            .await
+            .map(ApiResult::into_results)
+            .context("fetching the provided url failed")
+    }
+
+    /// Find the first tag for a given repository on the Docker Hub that matches a predicate
+    ///
+    /// This method walks through the tags page by page, and returns the first tag for which
+    /// `predicate` returns true, stopping the pagination right away; so that the rest of the
+    /// pages are not fetched when only a single match is needed, e.g. the first tag that has
+    /// an arm64 image. Returns `None` if none of the tags matches the predicate.
+    pub async fn find_tag<F>(
+        &self,
+        org: &str,
+        repository: &str,
+        predicate: F,
+    ) -> anyhow::Result<Option<Tag>>
+    where
+        F: Fn(&Tag) -> bool,
+    {
+        let url = self.tags_url(org, repository)?;
+
+        let mut found = None;
+        walk_pages::<Tag, _>(self, &url, |tags| {
+            match tags.into_iter().find(|tag| predicate(tag)) {
+                Some(tag) => {
+                    found = Some(tag);
+                    ControlFlow::Break(())
+                }
+                None => ControlFlow::Continue(()),
+            }
+        })
+        .await
+        .context("fetching the provided url failed")?;
+        Ok(found)
+    }
+
+    /// List the tags for a given repository on the Docker Hub pushed within a date range
+    ///
+    /// This method returns the tags whose last push happened within `[from, to]`, requesting
+    /// the tags ordered by their last update (most recent first) so that the pagination stops
+    /// as soon as the tags fall before `from`; avoiding fetching the whole tag history when
+    /// only a narrow window is needed.
+    pub async fn tags_in_range(
+        &self,
+        org: &str,
+        repository: &str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> anyhow::Result<Vec<Tag>> {
+        let mut url = self.tags_url(org, repository)?;
+        url.query_pairs_mut()
+            .append_pair("ordering", "last_updated");
+
+        let mut tags = Vec::new();
+        walk_pages::<Tag, _>(self, &url, |page| {
+            // As the tags are sorted by the last update in descending order, and a push bumps
+            // the last update, once the oldest push within the page happened before `from`, so
+            // did the rest; whereas the tags with no push time are skipped rather than stopping
+            let exhausted = match page.iter().rev().find_map(|tag| tag.tag_last_pushed) {
+                Some(pushed) => pushed < from,
+                None => page.is_empty(),
+            };
+            tags.extend(page.into_iter().filter(|tag| {
+                tag.tag_last_pushed
+                    .is_some_and(|pushed| pushed >= from && pushed <= to)
+            }));
+            if exhausted {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        })
+        .await
+        .context("fetching the provided url failed")?;
+        Ok(tags)
+    }
+
+    /// Compute the oldest and most recent activity for a given repository on the Docker Hub
+    ///
+    /// This method walks through the tags of the repository in a single pagination pass, and
+    /// computes the oldest and the most recent push time across those, as well as the number
+    /// of tags; which is useful for lifecycle dashboards e.g. to spot abandoned repositories.
+    pub async fn repository_activity(&self, org: &str, name: &str) -> anyhow::Result<RepoActivity> {
+        let url = self.tags_url(org, name)?;
+
+        let mut activity = RepoActivity::default();
+        walk_pages::<Tag, _>(self, &url, |tags| {
+            activity.tag_count += tags.len();
+            for pushed in tags.iter().filter_map(|tag| tag.tag_last_pushed) {
+                activity.first_pushed = Some(
+                    activity
+                        .first_pushed
+                        .map_or(pushed, |first| first.min(pushed)),
+                );
+                activity.last_pushed =
+                    Some(activity.last_pushed.map_or(pushed, |last| last.max(pushed)));
+            }
+            ControlFlow::Continue(())
+        })
+        .await
+        .context("fetching the provided url failed")?;
+        Ok(activity)
+    }
+
+    /// Export all the tags for a given repository on the Docker Hub as JSON lines
+    ///
+    /// This method writes one JSON object per tag and line into the provided `writer`, with the
+    /// name, digest, size, last push time and platforms of the tag e.g. "linux/amd64"; as soon as
+    /// each page is fetched, so that the memory usage stays flat even for huge repositories.
+    pub async fn tags_to_jsonl(
+        &self,
+        org: &str,
+        repository: &str,
+        mut writer: impl Write,
+    ) -> anyhow::Result<()> {
+        let url = self.tags_url(org, repository)?;
+
+        let mut result = Ok(());
+        walk_pages::<Tag, _>(self, &url, |tags| {
+            result = write_jsonl(&tags, &mut writer);
+            if result.is_err() {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        })
+        .await
+        .context("fetching the provided url failed")?;
+        result?;
+
+        writer
+            .flush()
+            .context("failed flushing the provided writer")
+    }
+
+    /// Find the tags whose names collide case-insensitively for a given repository
+    ///
+    /// This method groups the tags whose names only differ in casing e.g. "V1.0" and "v1.0",
+    /// returning only the groups with more than one tag; which helps to identify the accidental
+    /// duplicate tags e.g. before a migration.
+    pub async fn find_tag_collisions(
+        &self,
+        org: &str,
+        repository: &str,
+    ) -> anyhow::Result<Vec<Vec<String>>> {
+        let tags = self.list_tags(org, repository).await?;
+        Ok(case_collisions(tags.iter().map(Tag::name)))
+    }
+
+    /// List the platforms supported by each tag for a given repository on the Docker Hub
+    ///
+    /// This method flattens the images of each tag into one `(tag, platform)` row per platform,
+    /// producing a tidy table to render e.g. a compatibility matrix. The tags are processed as
+    /// soon as each page is fetched, so only the rows are kept in memory.
+    pub async fn tag_platform_matrix(
+        &self,
+        org: &str,
+        repository: &str,
+    ) -> anyhow::Result<Vec<(String, Platform)>> {
+        let url = self.tags_url(org, repository)?;
+
+        let mut matrix = Vec::new();
+        walk_pages::<Tag, _>(self, &url, |tags| {
+            for tag in tags {
+                matrix.extend(
+                    tag.images
+                        .iter()
+                        .map(|image| (tag.name.clone(), image.platform())),
+                );
+            }
+            ControlFlow::Continue(())
+        })
+        .await
+        .context("fetching the provided url failed")?;
+        Ok(matrix)
+    }
+
+    /// Returns the URL to list the tags for a given repository on the Docker Hub
+    fn tags_url(&self, org: &str, repository: &str) -> anyhow::Result<Url> {
+        let (org, repository) = resolve_repository(org, repository);
+        self.url
             .join(&format!(
                 "v2/namespaces/{}/repositories/{}/tags", // For some reason the endpoint `v2/repositories/{}/{}/tags` works seamlessly
                 org, repository
             ))
-            .context("failed formatting the url with the provided org and repository")?;
+            .context("failed formatting the url with the provided org and repository")
+    }
 
-        fetch_with_pagination::<Tag>(&self.client, &url)
-            .await
-            .context("fetching the provided url failed")
+    /// List the tags for a given repository on the Docker Hub that match the given media type
+    ///
+    /// This method lists all the tags via `list_tags` and only keeps the ones whose media type
+    /// matches the provided `media_type` e.g. `MediaType::OciImageIndex.as_str()` to only keep
+    /// the multi-platform OCI images.
+    pub async fn list_tags_by_media_type(
+        &self,
+        org: &str,
+        repository: &str,
+        media_type: &str,
+    ) -> Result<Vec<Tag>, HubError> {
+        let tags = self.list_tags(org, repository).await?;
+        Ok(tags
+            .into_iter()
+            .filter(|tag| tag.media_type == media_type)
+            .collect())
+    }
+
+    /// List the tags for a given repository on the Docker Hub that were last updated by a user
+    ///
+    /// This method lists all the tags via `list_tags` and only keeps the ones whose last updater
+    /// matches the provided `username`, which is useful to e.g. review the changes pushed by a
+    /// given user.
+    pub async fn list_tags_by_updater(
+        &self,
+        org: &str,
+        repository: &str,
+        username: &str,
+    ) -> Result<Vec<Tag>, HubError> {
+        let tags = self.list_tags(org, repository).await?;
+        Ok(tags
+            .into_iter()
+            .filter(|tag| tag.last_updater_username == username)
+            .collect())
     }
 
     pub async fn read_tag(&self, org: &str, repository: &str, tag: &str) -> anyhow::Result<Tag> {
+        self.read_tag_with_options(org, repository, tag, &SendOptions::default())
+            .await
+    }
+
+    /// Same as `read_tag` but sending the request with the provided `options`
+    async fn read_tag_with_options(
+        &self,
+        org: &str,
+        repository: &str,
+        tag: &str,
+        options: &SendOptions,
+    ) -> anyhow::Result<Tag> {
+        let (org, repository) = resolve_repository(org, repository);
         let url = self
             .url
             .join(&format!(
@@ -84,10 +662,136 @@ impl DockerHubClient {
             ))
             .context("failed formatting the url with the provided org, repository, and tag")?;
 
-        fetch::<Tag>(&self.client, &url, None, None)
+        fetch_with_options::<Tag>(self, &url, options)
+            .await
+            .context("fetching the provided url failed")
+    }
+
+    /// Wait until a given tag of a repository appears on the Docker Hub
+    ///
+    /// This method reads the tag every `poll_interval` until it exists, returning it, or until
+    /// the `timeout` elapses, returning an error; which is useful on CI pipelines that deploy
+    /// right after pushing. When the Docker Hub rate limits the polls, the next poll waits
+    /// until the time reported via the `X-Retry-After` header instead; whereas any failure other
+    /// than the tag not being found or the rate limit e.g. a 401, is returned right away. Each
+    /// poll is sent only once i.e. without the retries of the client, so that those don't
+    /// overshoot the `timeout`.
+    pub async fn wait_for_tag(
+        &self,
+        org: &str,
+        repository: &str,
+        tag: &str,
+        timeout: Duration,
+        poll_interval: Duration,
+    ) -> anyhow::Result<Tag> {
+        let deadline = saturating_add(self.clock.now(), timeout);
+        loop {
+            let error = match self
+                .read_tag_with_options(org, repository, tag, &NO_RETRIES)
+                .await
+            {
+                Ok(tag) => return Ok(tag),
+                Err(error) => error,
+            };
+
+            let now = self.clock.now();
+            let delay = match error.downcast_ref::<HubError>().map(HubError::inner) {
+                Some(HubError::RateLimited {
+                    retry_after: Some(retry_after),
+                    ..
+                }) => (*retry_after - now).to_std().unwrap_or(poll_interval),
+                Some(HubError::RateLimited { .. } | HubError::NotFound(_)) => poll_interval,
+                _ => return Err(error),
+            };
+            if saturating_add(now, delay) > deadline {
+                let (org, repository) = resolve_repository(org, repository);
+                return Err(error.context(format!(
+                    "timed out after {timeout:?} waiting for {org}/{repository}:{tag}"
+                )));
+            }
+            self.clock.sleep(delay).await;
+        }
+    }
+
+    /// Same as `read_tag` but for a repository under the default namespace of the client
+    pub async fn read_tag_in(&self, repository: &str, tag: &str) -> anyhow::Result<Tag> {
+        self.read_tag(self.require_default_namespace()?, repository, tag)
+            .await
+    }
+
+    /// Check whether a given tag of a repository on the Docker Hub points to the provided digest
+    ///
+    /// This method reads the tag and compares its top-level digest i.e. the one of the manifest
+    /// or the image index, with the provided `digest`; useful to detect deployment drift, e.g.
+    /// to confirm that a running image still corresponds to the expected tag.
+    pub async fn tag_matches_digest(
+        &self,
+        org: &str,
+        repository: &str,
+        tag: &str,
+        digest: &str,
+    ) -> anyhow::Result<bool> {
+        let tag = self.read_tag(org, repository, tag).await?;
+        Ok(tag.digest == digest)
+    }
+
+    /// List the images for a given tag of a repository on the Docker Hub
+    ///
+    /// This method hits the dedicated images sub-endpoint for a given tag, which contains richer
+    /// per-platform information than the `images` embedded within the tag listing; useful when
+    /// the embedded images are truncated or lack certain fields.
+    pub async fn tag_images(
+        &self,
+        org: &str,
+        repository: &str,
+        tag: &str,
+    ) -> anyhow::Result<Vec<Image>> {
+        let (org, repository) = resolve_repository(org, repository);
+        let url = self
+            .url
+            .join(&format!(
+                "v2/repositories/{}/{}/tags/{}/images",
+                org, repository, tag
+            ))
+            .context("failed formatting the url with the provided org, repository, and tag")?;
+
+        fetch::<Vec<Image>>(self, &url, None, None)
             .await
             .context("fetching the provided url failed")
     }
+
+    /// Resolve a set of tags of a given repository on the Docker Hub to their digests
+    ///
+    /// This method reads each of the provided `tags` concurrently, with a bounded parallelism
+    /// to avoid hitting the rate limits, and returns a map from the tag name to its top-level
+    /// digest. The tags that cannot be resolved e.g. because those don't exist, are reported
+    /// back as warnings rather than failing the whole resolution.
+    pub async fn resolve_digests(
+        &self,
+        org: &str,
+        repository: &str,
+        tags: &[&str],
+    ) -> anyhow::Result<Outcome<HashMap<String, String>>> {
+        let results = stream::iter(tags)
+            .map(|tag| async move { (*tag, self.read_tag(org, repository, tag).await) })
+            .buffer_unordered(self.max_concurrent_requests)
+            .collect::<Vec<_>>()
+            .await;
+
+        let mut outcome = Outcome::<HashMap<String, String>>::default();
+        for (name, result) in results {
+            match result {
+                Ok(tag) => {
+                    outcome.value.insert(name.to_string(), tag.digest);
+                }
+                Err(error) => outcome.warnings.push(Warning::ItemFailed {
+                    item: name.to_string(),
+                    error,
+                }),
+            }
+        }
+        Ok(outcome)
+    }
 }
 
 #[cfg(test)]
@@ -95,6 +799,8 @@ mod tests {
     use super::*;
     use serde_json::json;
 
+    use crate::tests::tag_json;
+
     #[test]
     fn test_tag_serde() {
         let value = json!({
@@ -134,9 +840,257 @@ mod tests {
             .context("failed to deserialize the tag payload")
             .unwrap();
 
+        assert_eq!(tag.media_type(), MediaType::OciImageIndex.as_str());
+        assert_eq!(tag.content_type(), "image");
+        assert_eq!(tag.last_updater_username(), "mxyng");
+
+        let image = &tag.images()[0];
+        assert_eq!(image.architecture(), "amd64");
+        assert_eq!(image.os(), Some("linux"));
+        assert_eq!(image.variant(), None);
+        assert_eq!(image.size(), tag.full_size());
+        assert!(image.digest().starts_with("sha256:96b6a4e6"));
+
         println!("{tag:#?}");
     }
 
+    #[test]
+    fn test_case_collisions() {
+        let names = ["latest", "V1.0", "v1.0", "v2.0", "Latest", "LATEST"];
+        assert_eq!(
+            case_collisions(names),
+            vec![
+                vec![
+                    "LATEST".to_string(),
+                    "Latest".to_string(),
+                    "latest".to_string()
+                ],
+                vec!["V1.0".to_string(), "v1.0".to_string()],
+            ]
+        );
+        assert!(case_collisions(["v1.0", "v2.0"]).is_empty());
+    }
+
+    #[test]
+    fn test_platform_display() {
+        let platform = Platform {
+            os: "linux".to_string(),
+            architecture: "arm64".to_string(),
+            variant: Some("v8".to_string()),
+        };
+        assert_eq!(platform.to_string(), "linux/arm64/v8");
+
+        let platform = Platform {
+            variant: None,
+            ..platform
+        };
+        assert_eq!(platform.to_string(), "linux/arm64");
+    }
+
+    #[test]
+    fn test_write_jsonl() {
+        let mut value = tag_json("gguf");
+        value["images"][0]["architecture"] = json!("arm64");
+        value["images"][0]["variant"] = json!("v8");
+        value["tag_last_pushed"] = json!("2024-01-16T20:54:55Z");
+        let tag = serde_json::from_value::<Tag>(value).unwrap();
+
+        let mut buffer = Vec::new();
+        write_jsonl(&[tag], &mut buffer).unwrap();
+
+        let output = String::from_utf8(buffer).unwrap();
+        assert_eq!(output.lines().count(), 1);
+
+        let record = serde_json::from_str::<serde_json::Value>(output.trim_end()).unwrap();
+        assert_eq!(record["name"], "gguf");
+        assert_eq!(record["size"], 755930694);
+        assert_eq!(record["last_pushed"], "2024-01-16T20:54:55Z");
+        assert_eq!(record["platforms"], json!(["linux/arm64/v8"]));
+    }
+
+    #[test]
+    fn test_tag_serde_camel_case() {
+        let value = json!({
+          "creator": 14304909,
+          "id": 529481097,
+          "images": [
+            {
+              "architecture": "amd64",
+              "features": "",
+              "variant": null,
+              "digest": "sha256:96b6a4e66250499a9d87a4adf259ced7cd213e2320fb475914217f4d69abe98d",
+              "os": "linux",
+              "osFeatures": "",
+              "osVersion": null,
+              "size": 755930694,
+              "status": "active",
+              "lastPulled": "2025-03-05T07:52:00.613197154Z",
+              "lastPushed": "2024-01-16T20:54:52Z"
+            }
+          ],
+          "lastUpdated": "2024-01-16T20:54:55.914808Z",
+          "lastUpdater": 14304909,
+          "lastUpdaterUsername": "mxyng",
+          "name": "gguf",
+          "repository": 22180121,
+          "fullSize": 755930694,
+          "v2": true,
+          "tagStatus": "active",
+          "tagLastPulled": "2025-03-05T07:52:00.613197154Z",
+          "tagLastPushed": "2024-01-16T20:54:55.914808Z",
+          "mediaType": "application/vnd.oci.image.index.v1+json",
+          "contentType": "image",
+          "digest": "sha256:7c49490a9e4a7ca4326e09c4b47bc525aa0a9dfc8ea0b3a30d62af23a60db712"
+        });
+
+        let tag = serde_json::from_value::<Tag>(value)
+            .context("failed to deserialize the camel case tag payload")
+            .unwrap();
+
+        assert_eq!(tag.media_type(), MediaType::OciImageIndex.as_str());
+        assert_eq!(tag.last_updater_username(), "mxyng");
+        assert!(tag.tag_last_pushed.is_some());
+
+        println!("{tag:#?}");
+    }
+
+    #[tokio::test]
+    async fn test_tags_in_range() {
+        use std::sync::atomic::Ordering;
+
+        use crate::tests::{json_response, mock_server_with};
+
+        let tag = |name: &str, pushed: Option<&str>| {
+            let mut value = tag_json(name);
+            value["last_updated"] = json!(pushed);
+            value["tag_last_pushed"] = json!(pushed);
+            value
+        };
+        let page = |next: Option<String>, tags: Vec<Value>| {
+            json_response(&json!({ "count": 6, "next": next, "previous": null, "results": tags }))
+        };
+
+        let (url, requests) = mock_server_with(|url| {
+            let next = |page: usize| Some(format!("{url}v2/tags?page={page}"));
+            vec![
+                page(
+                    next(2),
+                    vec![
+                        tag("0.6.1", Some("2025-03-12T00:00:00Z")),
+                        tag("0.6.0", Some("2025-03-09T00:00:00Z")),
+                        tag("untracked", None),
+                    ],
+                ),
+                page(
+                    next(3),
+                    vec![
+                        tag("0.5.13", Some("2025-03-06T00:00:00Z")),
+                        tag("0.5.12", Some("2025-03-01T00:00:00Z")),
+                    ],
+                ),
+                page(None, vec![tag("0.5.11", Some("2025-03-07T00:00:00Z"))]),
+            ]
+        })
+        .await;
+        let client = DockerHubClient::builder().base_url(url).build().unwrap();
+
+        let from = DateTime::parse_from_rfc3339("2025-03-05T00:00:00Z").unwrap();
+        let to = DateTime::parse_from_rfc3339("2025-03-10T00:00:00Z").unwrap();
+        let tags = client
+            .tags_in_range("ollama", "ollama", from.to_utc(), to.to_utc())
+            .await
+            .unwrap();
+
+        // The tag with no push time doesn't stop the pagination, whereas the page with a push
+        // before `from` does, so the last page is never fetched
+        let names: Vec<_> = tags.iter().map(Tag::name).collect();
+        assert_eq!(names, vec!["0.6.0", "0.5.13"]);
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_tag() {
+        use std::sync::atomic::Ordering;
+
+        use crate::clock::MockClock;
+        use crate::tests::{http_response, mock_server};
+
+        // A missing tag is polled until the timeout elapses
+        let start = DateTime::from_timestamp(1741600000, 0).unwrap();
+        let clock = MockClock::new(start);
+        let (url, requests) = mock_server(vec![http_response("404 Not Found", &[], "")]).await;
+        let client = DockerHubClient::builder()
+            .base_url(url)
+            .clock(clock.clone())
+            .build()
+            .unwrap();
+        let error = client
+            .wait_for_tag(
+                "ollama",
+                "ollama",
+                "0.6.0",
+                Duration::from_secs(30),
+                Duration::from_secs(10),
+            )
+            .await
+            .unwrap_err();
+        assert!(error.to_string().starts_with("timed out after 30s"));
+        assert_eq!(requests.load(Ordering::SeqCst), 4);
+        assert_eq!(clock.sleeps(), vec![Duration::from_secs(10); 3]);
+
+        // Whereas any other failure is returned right away, even with an unbounded timeout
+        let (url, requests) = mock_server(vec![http_response("401 Unauthorized", &[], "")]).await;
+        let client = DockerHubClient::builder()
+            .base_url(url)
+            .clock(MockClock::new(start))
+            .build()
+            .unwrap();
+        let error = client
+            .wait_for_tag(
+                "ollama",
+                "ollama",
+                "0.6.0",
+                Duration::MAX,
+                Duration::from_secs(10),
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<HubError>().map(HubError::inner),
+            Some(HubError::Unauthorized(_))
+        ));
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+
+        // A rate limit lifted past the deadline times out right away rather than waiting for it,
+        // neither within `wait_for_tag` nor within the retries of the request itself
+        let retry_after = (start + TimeDelta::hours(1)).timestamp().to_string();
+        let (url, requests) = mock_server(vec![http_response(
+            "429 Too Many Requests",
+            &[("X-Retry-After", retry_after)],
+            "",
+        )])
+        .await;
+        let clock = MockClock::new(start);
+        let client = DockerHubClient::builder()
+            .base_url(url)
+            .clock(clock.clone())
+            .build()
+            .unwrap();
+        let error = client
+            .wait_for_tag(
+                "ollama",
+                "ollama",
+                "0.6.0",
+                Duration::from_secs(60),
+                Duration::from_secs(10),
+            )
+            .await
+            .unwrap_err();
+        assert!(error.to_string().starts_with("timed out after 60s"));
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+        assert!(clock.sleeps().is_empty());
+    }
+
     #[tokio::test]
     async fn test_list_tags() -> anyhow::Result<()> {
         let pat =
@@ -0,0 +1,77 @@
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use crate::{fetch, DockerHubClient};
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct User {
+    /// The Docker ID of the user
+    id: String,
+
+    /// The username of the user on the Docker Hub
+    username: String,
+}
+
+impl User {
+    /// Returns the Docker ID of the user
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Returns the username of the user
+    pub fn username(&self) -> &str {
+        &self.username
+    }
+}
+
+impl DockerHubClient {
+    /// Get the username of the user authenticated with the provided token
+    ///
+    /// This method resolves the username of the authenticated user on the first call, and caches
+    /// it on the client so that the subsequent calls don't send any request; which is useful for
+    /// the operations over the personal namespace of the authenticated user.
+    pub async fn whoami(&self) -> anyhow::Result<String> {
+        let username = self
+            .username
+            .get_or_try_init(|| async {
+                let url = self
+                    .url
+                    .join("v2/user/")
+                    .context("failed formatting the user url")?;
+
+                let user = fetch::<User>(self, &url, None, None)
+                    .await
+                    .context("fetching the provided url failed")?;
+                anyhow::Ok(user.username)
+            })
+            .await?;
+        Ok(username.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_user_serde() {
+        let value = json!({
+          "id": "8b5e4d1c-3f0a-4d6e-9c1b-2a7f6e5d4c3b",
+          "username": "alvarobartt",
+          "full_name": "Alvaro Bartolome",
+          "location": "",
+          "company": "",
+          "date_joined": "2019-06-29T23:27:34.326426Z",
+          "type": "User"
+        });
+
+        let user = serde_json::from_value::<User>(value)
+            .context("failed to deserialize the user payload")
+            .unwrap();
+
+        assert_eq!(user.username(), "alvarobartt");
+
+        println!("{user:#?}");
+    }
+}